@@ -0,0 +1,122 @@
+use std::{fmt, io};
+
+use crate::Span;
+
+// Error codes are grouped by the stage that reports them:
+//   E01xx - Lexer
+//   E02xx - Abstracter
+//   E03xx - Parser
+//   E04xx - Generator
+//   E05xx - driver (arguments, files, assembler)
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Severity {
+    Error,
+    #[allow(unused)]
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Error => write!(f, "error"),
+            Self::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Diagnostic {
+    pub(crate) severity: Severity,
+    pub(crate) code: &'static str,
+    pub(crate) message: String,
+    pub(crate) span: Option<Span>,
+    pub(crate) notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub(crate) fn error(code: &'static str, message: impl Into<String>, span: &Span) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            code,
+            message: message.into(),
+            span: Some(span.clone()),
+            notes: Vec::new(),
+        }
+    }
+
+    // for errors that do not point into a source file
+    pub(crate) fn error_without_span(code: &'static str, message: impl Into<String>) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            code,
+            message: message.into(),
+            span: None,
+            notes: Vec::new(),
+        }
+    }
+
+    pub(crate) fn with_note(mut self, note: impl Into<String>) -> Diagnostic {
+        self.notes.push(note.into());
+        self
+    }
+
+    // rustc-style rendering:
+    //
+    // error[E0302]: not a name
+    //  --> input.tp:3:5
+    //   |
+    // 3 |     o weka e 2 + Y
+    //   |     ^
+    //   = note: ...
+    pub(crate) fn render(&self, source: Option<&str>) -> String {
+        let mut result = format!("{}[{}]: {}\n", self.severity, self.code, self.message);
+
+        let line = self
+            .span
+            .as_ref()
+            .and_then(|span| Some((span, source?.lines().nth(span.line.checked_sub(1)?)?)));
+
+        let gutter = match &line {
+            Some((span, _)) => span.line.to_string().len(),
+            None => 1,
+        };
+        let padding = " ".repeat(gutter);
+
+        if let Some(span) = &self.span {
+            result += &format!("{padding}--> {span}\n");
+        }
+
+        if let Some((span, text)) = line {
+            // keep tabs so the caret lines up with the source line
+            let marker: String = text
+                .chars()
+                .take(span.column.saturating_sub(1))
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+
+            result += &format!("{padding} |\n");
+            result += &format!("{} | {text}\n", span.line);
+            result += &format!("{padding} | {marker}^\n");
+        }
+
+        for note in &self.notes {
+            result += &format!("{padding} = note: {note}\n");
+        }
+
+        result
+    }
+}
+
+// lets `?` turn a single diagnostic into the list the driver reports
+impl From<Diagnostic> for Vec<Diagnostic> {
+    fn from(diagnostic: Diagnostic) -> Vec<Diagnostic> {
+        vec![diagnostic]
+    }
+}
+
+impl From<io::Error> for Diagnostic {
+    fn from(err: io::Error) -> Diagnostic {
+        Diagnostic::error_without_span("E0501", format!("could not write output: {err}"))
+    }
+}
@@ -1,4 +1,40 @@
-use std::{collections::HashMap, fs, rc::Rc, env, io::BufWriter, io::Write, process::Command};
+use std::{cmp::Ordering, collections::HashMap, fmt, fs, rc::Rc, env, io::BufWriter, io::Write, path::{Path, PathBuf}, process::{self, Command}};
+
+mod diagnostic;
+
+use diagnostic::Diagnostic;
+
+#[derive(Debug, Clone)]
+struct Span {
+    file: Rc<str>,
+    line: usize,
+    column: usize,
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+// A word or token together with the place in the source it starts at
+#[derive(Debug)]
+struct Spanned<T> {
+    value: T,
+    span: Span,
+    // comments right before it, for tools that want the source back
+    #[allow(unused)]
+    trivia: Vec<Comment>,
+}
+
+// '// text' or '/* text */', without the markers
+#[derive(Debug)]
+#[allow(unused)]
+struct Comment {
+    text: String,
+    block: bool,
+    span: Span,
+}
 
 #[derive(Debug, PartialEq, Eq)]
 #[allow(unused)]
@@ -22,11 +58,19 @@ enum Word {
     Unequals,
     LessThan,
     GreaterThan,
+    LessThanEquals,
+    GreaterThanEquals,
     Plus,
     Minus,
     Star,
     ForwardSlash,
     BackSlash,
+    Percent,
+    Ampersand,
+    Pipe,
+    Caret,
+    ShiftLeft,
+    ShiftRight,
     A,
     Ala,
     Alasa,
@@ -84,10 +128,16 @@ enum Word {
     Weka,
 }
 
-#[derive(PartialEq, PartialOrd)]
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
 enum Precedence {
     Undefined = 0,
+    Disjunctive,
+    Conjunctive,
     Comparing,
+    BitwiseOr,
+    BitwiseXor,
+    BitwiseAnd,
+    Shifting,
     Linear,
     Scaling,
     Unary,
@@ -97,8 +147,14 @@ enum Precedence {
 impl Precedence {
     fn next(&self) -> Precedence {
         match self {
-            Self::Undefined => Self::Comparing,
-            Self::Comparing => Self::Linear,
+            Self::Undefined => Self::Disjunctive,
+            Self::Disjunctive => Self::Conjunctive,
+            Self::Conjunctive => Self::Comparing,
+            Self::Comparing => Self::BitwiseOr,
+            Self::BitwiseOr => Self::BitwiseXor,
+            Self::BitwiseXor => Self::BitwiseAnd,
+            Self::BitwiseAnd => Self::Shifting,
+            Self::Shifting => Self::Linear,
             Self::Linear => Self::Scaling,
             Self::Scaling => Self::Unary,
             Self::Unary => Self::TheBiggest,
@@ -107,36 +163,274 @@ impl Precedence {
     }
 }
 
-fn get_precedence(binary_expression_type: BinaryExpressionType) -> Precedence {
-    return match binary_expression_type {
-        BinaryExpressionType::Add | BinaryExpressionType::Subtract => Precedence::Linear,
-        BinaryExpressionType::Multiply | BinaryExpressionType::Divide => Precedence::Scaling,
-        BinaryExpressionType::GreaterThan
-        | BinaryExpressionType::Equals
-        | BinaryExpressionType::LessThan => Precedence::Comparing,
-    };
+#[derive(Clone, Copy, PartialEq)]
+#[allow(unused)]
+enum Associativity {
+    // a - b - c is (a - b) - c
+    Left,
+    // for an operator that groups from the right, like a power where 2 ** 3 ** 2 is 2 ** (3 ** 2).
+    // none of the operators in BINARY_OPERATORS does yet
+    Right,
+}
+
+struct BinaryOperator {
+    token: Token,
+    kind: BinaryExpressionType,
+    precedence: Precedence,
+    associativity: Associativity,
+}
+
+// every binary operator the parser knows, a new operator only needs a line here
+static BINARY_OPERATORS: &[BinaryOperator] = &[
+    BinaryOperator { token: Token::Anu, kind: BinaryExpressionType::Or, precedence: Precedence::Disjunctive, associativity: Associativity::Left },
+    BinaryOperator { token: Token::En, kind: BinaryExpressionType::And, precedence: Precedence::Conjunctive, associativity: Associativity::Left },
+    BinaryOperator { token: Token::Equals, kind: BinaryExpressionType::Equals, precedence: Precedence::Comparing, associativity: Associativity::Left },
+    BinaryOperator { token: Token::DoubleEquals, kind: BinaryExpressionType::Equals, precedence: Precedence::Comparing, associativity: Associativity::Left },
+    BinaryOperator { token: Token::Unequals, kind: BinaryExpressionType::Unequals, precedence: Precedence::Comparing, associativity: Associativity::Left },
+    BinaryOperator { token: Token::LessThan, kind: BinaryExpressionType::LessThan, precedence: Precedence::Comparing, associativity: Associativity::Left },
+    BinaryOperator { token: Token::GreaterThan, kind: BinaryExpressionType::GreaterThan, precedence: Precedence::Comparing, associativity: Associativity::Left },
+    BinaryOperator { token: Token::LessThanEquals, kind: BinaryExpressionType::LessEquals, precedence: Precedence::Comparing, associativity: Associativity::Left },
+    BinaryOperator { token: Token::GreaterThanEquals, kind: BinaryExpressionType::GreaterEquals, precedence: Precedence::Comparing, associativity: Associativity::Left },
+    BinaryOperator { token: Token::Pipe, kind: BinaryExpressionType::BitOr, precedence: Precedence::BitwiseOr, associativity: Associativity::Left },
+    BinaryOperator { token: Token::Caret, kind: BinaryExpressionType::BitXor, precedence: Precedence::BitwiseXor, associativity: Associativity::Left },
+    BinaryOperator { token: Token::Ampersand, kind: BinaryExpressionType::BitAnd, precedence: Precedence::BitwiseAnd, associativity: Associativity::Left },
+    BinaryOperator { token: Token::ShiftLeft, kind: BinaryExpressionType::ShiftLeft, precedence: Precedence::Shifting, associativity: Associativity::Left },
+    BinaryOperator { token: Token::ShiftRight, kind: BinaryExpressionType::ShiftRight, precedence: Precedence::Shifting, associativity: Associativity::Left },
+    BinaryOperator { token: Token::Plus, kind: BinaryExpressionType::Add, precedence: Precedence::Linear, associativity: Associativity::Left },
+    BinaryOperator { token: Token::Minus, kind: BinaryExpressionType::Subtract, precedence: Precedence::Linear, associativity: Associativity::Left },
+    BinaryOperator { token: Token::Star, kind: BinaryExpressionType::Multiply, precedence: Precedence::Scaling, associativity: Associativity::Left },
+    BinaryOperator { token: Token::ForwardSlash, kind: BinaryExpressionType::Divide, precedence: Precedence::Scaling, associativity: Associativity::Left },
+    BinaryOperator { token: Token::Percent, kind: BinaryExpressionType::Modulo, precedence: Precedence::Scaling, associativity: Associativity::Left },
+];
+
+fn get_binary_operator(token: &Token) -> Option<&'static BinaryOperator> {
+    BINARY_OPERATORS
+        .iter()
+        .find(|operator| std::mem::discriminant(&operator.token) == std::mem::discriminant(token))
 }
 
 struct Lexer {
     debug_mode: bool,
+    file: Rc<str>,
     buffer: String,
     current_position: usize,
+    line: usize,
+    column: usize,
 }
 
 impl Lexer {
+    // current_position is a byte offset, so multi-byte characters are stepped over whole
     fn consume(&mut self) {
-        self.current_position += 1;
+        let c = self.peek();
+        if c == Some('\n') {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        self.current_position += c.map_or(1, char::len_utf8);
+    }
+
+    fn span(&self) -> Span {
+        Span {
+            file: self.file.clone(),
+            line: self.line,
+            column: self.column,
+        }
     }
 
     fn peek(&self) -> Option<char> {
-        self.buffer.chars().nth(self.current_position)
+        self.buffer[self.current_position..].chars().next()
+    }
+
+    fn peek_next(&self) -> Option<char> {
+        self.buffer[self.current_position..].chars().nth(1)
+    }
+
+    // "'text'", the current character is the opening quote
+    fn lex_string(&mut self, span: &Span) -> Result<String, Diagnostic> {
+        self.consume();
+        let mut string = String::new();
+        loop {
+            match self.peek() {
+                None => {
+                    return Err(Diagnostic::error("E0102", "Unterminated string literal", span)
+                        .with_note("a string has to end with '\"'"));
+                }
+                Some('"') => {
+                    self.consume();
+                    return Ok(string);
+                }
+                Some('\\') => string.push(self.lex_escape()?),
+                Some(c) => {
+                    string.push(c);
+                    self.consume();
+                }
+            }
+        }
+    }
+
+    // \n \t \r \0 \" \\ \xNN \u{NNNN}, the current character is the backslash
+    fn lex_escape(&mut self) -> Result<char, Diagnostic> {
+        let span = self.span();
+        self.consume();
+
+        let c = match self.peek() {
+            None => return Err(Diagnostic::error("E0102", "Unterminated string literal", &span)),
+            Some(c) => c,
+        };
+        self.consume();
+
+        match c {
+            'n' => Ok('\n'),
+            't' => Ok('\t'),
+            'r' => Ok('\r'),
+            '0' => Ok('\0'),
+            '"' => Ok('"'),
+            '\\' => Ok('\\'),
+            'x' => {
+                let mut digits = String::new();
+                for _ in 0..2 {
+                    match self.peek() {
+                        Some(digit) if digit.is_ascii_hexdigit() => {
+                            digits.push(digit);
+                            self.consume();
+                        }
+                        _ => break,
+                    }
+                }
+                match u8::from_str_radix(&digits, 16) {
+                    Ok(value) if digits.len() == 2 && value <= 0x7f => Ok(value as char),
+                    _ => Err(Diagnostic::error("E0104", format!("Invalid escape \\x{digits}"), &span)
+                        .with_note("\\x takes two hex digits up to 7f, use \\u{...} for anything above")),
+                }
+            }
+            'u' => {
+                let invalid = |digits: &str| {
+                    Diagnostic::error("E0105", format!("Invalid unicode escape \\u{{{digits}}}"), &span)
+                        .with_note("write it as \\u{...} with one to six hex digits naming a unicode character")
+                };
+                if self.peek() != Some('{') {
+                    return Err(invalid(""));
+                }
+                self.consume();
+
+                let mut digits = String::new();
+                loop {
+                    match self.peek() {
+                        Some('}') => {
+                            self.consume();
+                            break;
+                        }
+                        Some(digit) if digit.is_ascii_hexdigit() && digits.len() < 6 => {
+                            digits.push(digit);
+                            self.consume();
+                        }
+                        _ => return Err(invalid(&digits)),
+                    }
+                }
+                u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| invalid(&digits))
+            }
+            _ => Err(Diagnostic::error("E0103", format!("Unknown escape \\{c}"), &span)
+                .with_note("known escapes are \\n \\t \\r \\0 \\\" \\\\ \\xNN and \\u{...}")),
+        }
+    }
+
+    // r"'text'" or r#"'text'"# when the text contains quotes. no escapes, and it may span lines
+    fn lex_raw_string(&mut self, span: &Span) -> Result<String, Diagnostic> {
+        // r
+        self.consume();
+        let mut hashes = 0;
+        while self.peek() == Some('#') {
+            hashes += 1;
+            self.consume();
+        }
+        if self.peek() != Some('"') {
+            return Err(Diagnostic::error("E0106", "Expected '\"' to start the raw string", &self.span()));
+        }
+        self.consume();
+
+        let firstchar = self.current_position;
+        loop {
+            match self.peek() {
+                None => {
+                    return Err(Diagnostic::error("E0102", "Unterminated raw string literal", span)
+                        .with_note(format!("this raw string ends with '\"{}'", "#".repeat(hashes))));
+                }
+                Some('"') => {
+                    let end = self.current_position;
+                    self.consume();
+                    let mut closing = 0;
+                    while closing < hashes && self.peek() == Some('#') {
+                        closing += 1;
+                        self.consume();
+                    }
+                    if closing == hashes {
+                        return Ok(self.buffer[firstchar..end].to_string());
+                    }
+                }
+                Some(_) => self.consume(),
+            }
+        }
+    }
+
+    // '// text' up to the end of the line, or '/* text */' where block comments nest
+    fn lex_comment(&mut self, span: &Span) -> Result<Comment, Diagnostic> {
+        self.consume();
+        let block = self.peek() == Some('*');
+        self.consume();
+        let firstchar = self.current_position;
+
+        if !block {
+            while !matches!(self.peek(), None | Some('\n')) {
+                self.consume();
+            }
+            return Ok(Comment {
+                text: self.buffer[firstchar..self.current_position].to_string(),
+                block,
+                span: span.clone(),
+            });
+        }
+
+        let mut depth = 1;
+        loop {
+            match (self.peek(), self.peek_next()) {
+                (None, _) => {
+                    return Err(Diagnostic::error("E0107", "Unterminated block comment", span)
+                        .with_note(format!("{depth} '/*' still need a '*/'")));
+                }
+                (Some('/'), Some('*')) => {
+                    depth += 1;
+                    self.consume();
+                }
+                (Some('*'), Some('/')) => {
+                    depth -= 1;
+                    if depth == 0 {
+                        let end = self.current_position;
+                        self.consume();
+                        self.consume();
+                        return Ok(Comment {
+                            text: self.buffer[firstchar..end].to_string(),
+                            block,
+                            span: span.clone(),
+                        });
+                    }
+                    self.consume();
+                }
+                _ => {}
+            }
+            self.consume();
+        }
     }
 
-    fn lex(&mut self) -> Vec<Word> {
-        let mut words: Vec<Word> = Vec::new();
+    fn lex(&mut self) -> Result<Vec<Spanned<Word>>, Diagnostic> {
+        let mut words: Vec<Spanned<Word>> = Vec::new();
+        let mut comments: Vec<Comment> = Vec::new();
         let mut c;
 
-        let mut line_number = 0usize;
         let mut is_line_start = true;
 
         while self.current_position < self.buffer.len() {
@@ -144,8 +438,17 @@ impl Lexer {
                 None => break,
                 Some(ch) => ch,
             };
+            let span = self.span();
 
-            if c.is_alphabetic() || c == '_' {
+            if c == 'r' && matches!(self.peek_next(), Some('"' | '#')) {
+                is_line_start = false;
+                let string = self.lex_raw_string(&span)?;
+		words.push(Spanned {
+		    value: Word::StringLiteral(string),
+		    span,
+		    trivia: Vec::new(),
+		});
+            } else if c.is_alphabetic() || c == '_' {
                 is_line_start = false;
                 let firstchar = self.current_position;
                 loop {
@@ -169,6 +472,9 @@ impl Lexer {
                     "o" => Word::O,
                     "e" => Word::E,
                     "en" => Word::En,
+                    "anu" => Word::Anu,
+                    "ala" => Word::Ala,
+                    "lon" => Word::Lon,
                     "kama" => Word::Kama,
                     "li" => Word::Li,
                     "nanpa" => Word::Nanpa,
@@ -183,6 +489,7 @@ impl Lexer {
                     "tenpo" => Word::Tenpo,
                     "pini" => Word::Pini,
                     "awen" => Word::Awen,
+                    "lukin" => Word::Lukin,
                     "linja" => Word::Linja,
                     "asen" => Word::Asen,
                     "sama" => Word::Sama,
@@ -195,13 +502,16 @@ impl Lexer {
                     "luka" => Word::Luka,
                     "pana" => Word::Pana,
                     "pi" => Word::Pi,
+                    "mute" => Word::Mute,
+                    "nasin" => Word::Nasin,
+                    "pona" => Word::Pona,
                     _ => {
                         prefix = "name";
                         Word::Name(name.clone())
                     }
                 };
 
-                words.push(token);
+                words.push(Spanned { value: token, span, trivia: Vec::new() });
 
                 if self.debug_mode {
                     println!("{prefix}: {name}");
@@ -209,13 +519,22 @@ impl Lexer {
             } else if c.is_numeric() {
                 is_line_start = false;
                 let firstchar = self.current_position;
+                let mut has_period = false;
                 loop {
                     c = match self.peek() {
                         None => break,
                         Some(ch) => ch,
                     };
 
-                    if !c.is_alphanumeric() {
+                    // 1.5, but the period in 'o pali e 1.' still ends the statement
+                    if c == '.' && !has_period && self.peek_next().is_some_and(|next| next.is_ascii_digit()) {
+                        has_period = true;
+                        self.consume();
+                        continue;
+                    }
+
+                    // letters too, for 0x1f and for a helpful error on 12ab
+                    if !(c.is_alphanumeric() || c == '_') {
                         break;
                     }
 
@@ -224,29 +543,24 @@ impl Lexer {
 
                 let value = self.buffer[firstchar..self.current_position].to_string();
 
-                words.push(Word::Number(value.clone()));
+                words.push(Spanned {
+                    value: Word::Number(value.clone()),
+                    span,
+                    trivia: Vec::new(),
+                });
 
                 if self.debug_mode {
                     println!("number: {value}");
                 }
             } else if c == '"' {
                 is_line_start = false;
-                let firstchar = self.current_position;
-                loop {
-                    match self.peek() {
-                        None => break,
-                        Some(ch) => c = ch,
-                    }
-                    if c == '"' {
-                        break;
-                    }
-
-                    self.consume();
-                }
-                let string = self.buffer[firstchar..self.current_position].to_string();
-		words.push(Word::StringLiteral(string));
+                let string = self.lex_string(&span)?;
+		words.push(Spanned {
+		    value: Word::StringLiteral(string),
+		    span,
+		    trivia: Vec::new(),
+		});
             } else if c == '\n' {
-                line_number += 1;
                 is_line_start = true;
                 self.consume();
             } else if c == '\t' && is_line_start {
@@ -270,10 +584,36 @@ impl Lexer {
                     println!("found {} opening tabs", tab_count);
                 }
 
-                words.push(Word::OpeningTab(tab_count));
+                words.push(Spanned {
+                    value: Word::OpeningTab(tab_count),
+                    span,
+                    trivia: Vec::new(),
+                });
             } else if c.is_whitespace() {
                 is_line_start = false;
                 self.consume();
+            } else if c == '/' && matches!(self.peek_next(), Some('/' | '*')) {
+                let comment = self.lex_comment(&span)?;
+                if self.debug_mode {
+                    println!("comment: {}", comment.text);
+                }
+                comments.push(comment);
+            } else if let Some(token) = match (c, self.peek_next()) {
+                ('=', Some('=')) => Some(Word::DoubleEquals),
+                ('!', Some('=')) => Some(Word::Unequals),
+                ('<', Some('=')) => Some(Word::LessThanEquals),
+                ('>', Some('=')) => Some(Word::GreaterThanEquals),
+                ('<', Some('<')) => Some(Word::ShiftLeft),
+                ('>', Some('>')) => Some(Word::ShiftRight),
+                _ => None,
+            } {
+                is_line_start = false;
+                self.consume();
+                self.consume();
+                if self.debug_mode {
+                    println!("symbol: {:#?}", token);
+                }
+                words.push(Spanned { value: token, span, trivia: Vec::new() });
             } else {
                 is_line_start = false;
                 let token = match c {
@@ -290,25 +630,45 @@ impl Lexer {
                     '*' => Word::Star,
                     '.' => Word::Period,
                     '/' => Word::ForwardSlash,
+                    '%' => Word::Percent,
+                    '&' => Word::Ampersand,
+                    '|' => Word::Pipe,
+                    '^' => Word::Caret,
                     _ => {
-                        panic!(
-                            "Unexpected character {c} at position {}",
-                            self.current_position
-                        );
+                        return Err(Diagnostic::error(
+                            "E0101",
+                            format!("Unexpected character {c}"),
+                            &span,
+                        ));
                     }
                 };
                 self.consume();
                 if self.debug_mode {
                     println!("symbol: {:#?}", token);
                 }
-                words.push(token);
+                words.push(Spanned { value: token, span, trivia: Vec::new() });
+            }
+        }
+
+        // every comment goes to the word after it, the ones at the end of the file to the last word
+        let mut comments = comments.into_iter().peekable();
+        for word in words.iter_mut() {
+            while let Some(comment) = comments.next_if(|comment| {
+                (comment.span.line, comment.span.column) < (word.span.line, word.span.column)
+            }) {
+                word.trivia.push(comment);
             }
         }
-        words
+        if let Some(word) = words.last_mut() {
+            word.trivia.extend(comments);
+        }
+
+        Ok(words)
     }
 }
 
 #[derive(Debug)]
+#[allow(unused)]
 enum Token {
     // Composite tokens
     OTawa,
@@ -330,6 +690,10 @@ enum Token {
     En,
     Kepeken,
     A,
+    // Logic
+    Anu,
+    Ala,
+    Lon,
     
     // Punctuation
     Period,
@@ -357,6 +721,9 @@ enum Token {
     Telotu,
     Linja,
     Awen,
+    Lukin,
+    Signed,
+    Unsigned,
     // Parens
     OpenParenthesis,
     CloseParenthesis,
@@ -366,24 +733,54 @@ enum Token {
     Unequals,
     LessThan,
     GreaterThan,
+    LessThanEquals,
+    GreaterThanEquals,
     // Arithmetics
     Plus,
     Minus,
     Star,
     ForwardSlash,
     BackSlash,
+    Percent,
+    // Bitwise
+    Ampersand,
+    Pipe,
+    Caret,
+    ShiftLeft,
+    ShiftRight,
 }
 
 struct Abstracter {
-    words: Vec<Word>,
+    words: Vec<Spanned<Word>>,
     current_word: usize,
-    tokens: Vec<Token>,
+    tokens: Vec<Spanned<Token>>,
+    token_span: Span,
+    // first word of the token that is being built
+    token_start: usize,
+    number_system: NumberSystem,
+    diagnostics: Vec<Diagnostic>,
     debug_mode: bool,
 }
 
+#[derive(Debug, Clone, Copy)]
+enum NumberSystem {
+    // wan 1, tu 2, luka 5, mute 20, ale 100
+    Pona,
+    // wan 1, tu 2, mute 4 ("many")
+    Lili,
+}
+
 impl Abstracter {
     fn peek(&self) -> Option<&Word> {
-        self.words.get(self.current_word)
+        self.words.get(self.current_word).map(|word| &word.value)
+    }
+
+    // span of the current word, or of the last one once the words run out
+    fn span(&self) -> Span {
+        match self.words.get(self.current_word).or(self.words.last()) {
+            Some(word) => word.span.clone(),
+            None => self.token_span.clone(),
+        }
     }
 
     fn consume(&mut self) {
@@ -394,34 +791,37 @@ impl Abstracter {
         if self.debug_mode {
             println!("token: {:#?}", token);
         }
-        self.tokens.push(token);
+        // a composite token keeps the comments of all its words
+        let end = self.current_word.max(self.token_start + 1).min(self.words.len());
+        let trivia = self.words[self.token_start..end]
+            .iter_mut()
+            .flat_map(|word| std::mem::take(&mut word.trivia))
+            .collect();
+        self.tokens.push(Spanned {
+            value: token,
+            span: self.token_span.clone(),
+            trivia,
+        });
     }
 
+    #[allow(unused)]
     fn unconsume(&mut self) {
         self.current_word -= 1;
     }
 
+    // ala (zero) is left to the parser, since it is also the logical not
     fn is_number(word: &Word) -> bool {
-        match word {
-            Word::Wan | Word::Tu | Word::Luka => true,
-            _ => false,
-        }
+        matches!(word, Word::Wan | Word::Tu | Word::Luka | Word::Mute | Word::Ale)
     }
 
     fn expect(&self, expected: Word) -> bool {
         match self.peek() {
-            None => return false,
-            Some(word) => {
-                if std::mem::discriminant(word) == std::mem::discriminant(&expected) {
-                    return true;
-                } else {
-                    return false;
-                }
-            }
+            None => false,
+            Some(word) => std::mem::discriminant(word) == std::mem::discriminant(&expected),
         }
     }
 
-    fn tokenize_o(&mut self) {
+    fn tokenize_o(&mut self) -> Result<(), Diagnostic> {
         if !self.expect(Word::O) {
             unreachable!();
         }
@@ -432,19 +832,19 @@ impl Abstracter {
         if self.expect(Word::Tawa) {
             self.consume();
             self.push(Token::OTawa);
-            return;
+            return Ok(());
         }
 
         // o 'name of function'
         if self.expect(Word::Name("".to_string())) {
             self.push(Token::O);
-            return;
+            return Ok(());
         }
 
         if self.expect(Word::Weka) {
             self.consume();
             self.push(Token::OWeka);
-            return;
+            return Ok(());
         }
 
         // o sin e
@@ -453,36 +853,64 @@ impl Abstracter {
             if self.expect(Word::E) {
                 self.consume();
                 self.push(Token::OSin);
-                return;
+                return Ok(());
             }
-            panic!("No 'e' in 'o sin' statement");
+            return Err(Diagnostic::error(
+                "E0202",
+                "No 'e' in 'o sin' statement",
+                &self.span(),
+            ));
         }
 
         if self.expect(Word::Pini) {
             self.consume();
             self.push(Token::OPini);
-            return;
+            return Ok(());
         }
 
-        panic!("not a valid o statement");
+        Err(Diagnostic::error("E0203", "not a valid o statement", &self.token_span)
+            .with_note("expected 'o tawa', 'o weka', 'o sin e', 'o pini' or 'o' followed by a function name"))
     }
 
-    fn tokenize_nanpas(&mut self) {
+    // nasin nanpa pona adds the words up from the biggest to the smallest: mute mute luka tu = 47.
+    // nasin nanpa lili only has wan, tu and mute, there mute just means "many" and counts as 4
+    fn tokenize_nanpas(&mut self) -> Result<(), Diagnostic> {
         let mut result = 0;
+        let mut previous = None;
 
         loop {
-            result += match self.peek() {
-                None => break,
-                Some(word) => match word {
-                    Word::Wan => 1,
-                    Word::Tu => 2,
-                    Word::Luka => 5,
-                    _ => break,
-                },
+            let value = match (self.peek(), self.number_system) {
+                (Some(Word::Wan), _) => 1,
+                (Some(Word::Tu), _) => 2,
+                (Some(Word::Luka), NumberSystem::Pona) => 5,
+                (Some(Word::Mute), NumberSystem::Pona) => 20,
+                (Some(Word::Mute), NumberSystem::Lili) => 4,
+                (Some(Word::Ale), NumberSystem::Pona) => 100,
+                (Some(word @ (Word::Luka | Word::Ale)), NumberSystem::Lili) => {
+                    return Err(Diagnostic::error(
+                        "E0210",
+                        format!("{} is not a number in nasin nanpa lili", format!("{:?}", word).to_lowercase()),
+                        &self.span(),
+                    )
+                    .with_note("nasin nanpa lili only counts with wan, tu and mute, 'kepeken nasin nanpa pona' has luka and ale"));
+                }
+                _ => break,
             };
+
+            if previous.is_some_and(|previous| value > previous) {
+                return Err(Diagnostic::error(
+                    "E0211",
+                    "number words have to go from the biggest to the smallest",
+                    &self.span(),
+                )
+                .with_note("for example 'mute luka tu' is 27"));
+            }
+            previous = Some(value);
+            result += value;
             self.consume();
         }
         self.push(Token::Number(result.to_string()));
+        Ok(())
     }
 
     fn tokenize_name(&mut self) {
@@ -515,9 +943,38 @@ impl Abstracter {
         self.consume();
     }
 
-    fn tokenize_tenpo(&mut self) {
+    // kepeken nasin nanpa pona / lili picks how numbers are spelled from here on,
+    // any other kepeken is an import
+    fn tokenize_kepeken(&mut self) -> Result<(), Diagnostic> {
+        self.consume();
+
+        if !self.expect(Word::Nasin) {
+            self.push(Token::Kepeken);
+            return Ok(());
+        }
+        self.consume();
+
+        if !self.expect(Word::Nanpa) {
+            return Err(Diagnostic::error("E0212", "not a nasin nanpa", &self.span())
+                .with_note("expected 'kepeken nasin nanpa pona' or 'kepeken nasin nanpa lili'"));
+        }
+        self.consume();
+
+        self.number_system = match self.peek() {
+            Some(Word::Pona) => NumberSystem::Pona,
+            Some(Word::Lili) => NumberSystem::Lili,
+            _ => {
+                return Err(Diagnostic::error("E0212", "not a nasin nanpa", &self.span())
+                    .with_note("expected 'kepeken nasin nanpa pona' or 'kepeken nasin nanpa lili'"));
+            }
+        };
+        self.consume();
+        Ok(())
+    }
+
+    fn tokenize_tenpo(&mut self) -> Result<(), Diagnostic> {
         if !self.expect(Word::Tenpo) {
-            return;
+            return Ok(());
         }
         self.consume();
 
@@ -526,27 +983,32 @@ impl Abstracter {
             self.consume();
 
             if !self.expect(Word::Pi) {
-                panic!("no 'pi' in 'tenpo ale pi'");
+                return Err(Diagnostic::error(
+                    "E0204",
+                    "no 'pi' in 'tenpo ale pi'",
+                    &self.span(),
+                ));
             }
             self.consume();
 
             self.push(Token::TenpoAlePi);
-            return;
+            return Ok(());
         }
 
         // tenpo pi
         if self.expect(Word::Pi) {
             self.consume();
             self.push(Token::TenpoPi);
-            return;
+            return Ok(());
         }
 
-        panic!("not a valid tenpo statement");
+        Err(Diagnostic::error("E0205", "not a valid tenpo statement", &self.token_span)
+            .with_note("expected 'tenpo pi' or 'tenpo ale pi'"))
     }
 
-    fn tokenize_li(&mut self) {
+    fn tokenize_li(&mut self) -> Result<(), Diagnostic> {
         if !self.expect(Word::Li) {
-            return;
+            return Ok(());
         }
 
         self.consume();
@@ -556,14 +1018,18 @@ impl Abstracter {
             self.consume();
 
             if !self.expect(Word::Sama) {
-                panic!("no 'sama' in 'kama sama' statement");
+                return Err(Diagnostic::error(
+                    "E0206",
+                    "no 'sama' in 'kama sama' statement",
+                    &self.span(),
+                ));
             }
 
             self.consume();
 
             self.push(Token::LiKamaSama);
 
-            return;
+            return Ok(());
         }
 
         // li kepeken
@@ -572,47 +1038,71 @@ impl Abstracter {
 
             self.push(Token::LiKepeken);
 
-            return;
+            return Ok(());
         }
 
         // li pali e ni:
         if self.expect(Word::Pali) {
             self.consume();
             if !self.expect(Word::E) {
-                panic!("no 'e' in 'li pali e ni' token");
+                return Err(Diagnostic::error(
+                    "E0207",
+                    "no 'e' in 'li pali e ni' token",
+                    &self.span(),
+                ));
             }
             self.consume();
 
             if !self.expect(Word::Ni) {
-                panic!("no 'ni' in 'li pali e ni' token");
+                return Err(Diagnostic::error(
+                    "E0207",
+                    "no 'ni' in 'li pali e ni' token",
+                    &self.span(),
+                ));
             }
             self.consume();
             self.push(Token::LiPaliENi);
+            return Ok(());
         }
 
         if self.expect(Word::Pana) {
             self.consume();
             if !self.expect(Word::E) {
-                panic!("no 'e' in 'li pana e' token");
+                return Err(Diagnostic::error(
+                    "E0208",
+                    "no 'e' in 'li pana e' token",
+                    &self.span(),
+                ));
             }
             self.consume();
             self.push(Token::LiPanaE);
+            return Ok(());
         }
+
+        Err(Diagnostic::error("E0209", "not a valid li clause", &self.token_span)
+            .with_note("expected 'li kama sama', 'li kepeken', 'li pali e ni' or 'li pana e'"))
     }
 
     fn tokenize_arithmetics(&mut self) {
-        let word = match self.peek() {
-            None => unreachable!(),
-            Some(word) => word,
-        };
-
-        let token = match word {
-            Word::Plus => Token::Plus,
-            Word::Minus => Token::Minus,
-            Word::Star => Token::Star,
-            Word::ForwardSlash => Token::ForwardSlash,
-            Word::Equals => Token::Equals,
-            _ => todo!(),
+        let token = match self.peek() {
+            Some(Word::Plus) => Token::Plus,
+            Some(Word::Minus) => Token::Minus,
+            Some(Word::Star) => Token::Star,
+            Some(Word::ForwardSlash) => Token::ForwardSlash,
+            Some(Word::Percent) => Token::Percent,
+            Some(Word::Ampersand) => Token::Ampersand,
+            Some(Word::Pipe) => Token::Pipe,
+            Some(Word::Caret) => Token::Caret,
+            Some(Word::ShiftLeft) => Token::ShiftLeft,
+            Some(Word::ShiftRight) => Token::ShiftRight,
+            Some(Word::Equals) => Token::Equals,
+            Some(Word::DoubleEquals) => Token::DoubleEquals,
+            Some(Word::Unequals) => Token::Unequals,
+            Some(Word::LessThan) => Token::LessThan,
+            Some(Word::GreaterThan) => Token::GreaterThan,
+            Some(Word::LessThanEquals) => Token::LessThanEquals,
+            Some(Word::GreaterThanEquals) => Token::GreaterThanEquals,
+            _ => unreachable!(),
         };
 
         self.consume();
@@ -620,170 +1110,523 @@ impl Abstracter {
         self.push(token);
     }
 
+    // a word that can not be tokenized is reported and its line dropped, then tokenizing
+    // goes on with the next line, so one run finds every broken line without follow-up errors
     fn tokenize(&mut self) {
         while self.current_word < self.words.len() {
-            let word = match self.peek() {
-                None => return,
-                Some(word) => word,
-            };
-
-            match word {
-                Word::Name(_) => self.tokenize_name(),
-                Word::Number(_) => self.tokenize_number(),
-		Word::StringLiteral(string) => {
-		    self.push(Token::StringLiteral(string.to_string()));
-		    self.consume();
-		}
-                Word::O => self.tokenize_o(),
-                Word::Tenpo => self.tokenize_tenpo(),
-                Word::Plus | Word::Minus | Word::ForwardSlash | Word::Star | Word::Equals => {
-                    self.tokenize_arithmetics()
-                }
-                Word::Nanpa => {
-                    self.push(Token::Nanpa);
-                    self.consume();
-                }
-                Word::Period => {
-                    self.push(Token::Period);
-                    self.consume();
-                }
-                Word::Li => self.tokenize_li(),
-                Word::Kepeken => {
-                    self.push(Token::Kepeken);
-                    self.consume();
-                }
-                Word::En => {
-                    self.push(Token::En);
-                    self.consume();
-                }
-                Word::E => {
-                    self.push(Token::E);
-                    self.consume();
-                }
-		Word::A => {
-		    self.push(Token::A);
-		    self.consume();
-		}
-                Word::OpeningTab(tabs) => {
-                    self.push(Token::OpeningTab(*tabs));
-                    self.consume();
-                }
-                Word::Pali => {
-                    self.push(Token::Pali);
-                    self.consume();
-                }
-                Word::OpenParenthesis => {
-                    self.push(Token::OpenParenthesis);
-                    self.consume();
-                }
-                Word::CloseParenthesis => {
-                    self.push(Token::CloseParenthesis);
-                    self.consume();
+            self.token_span = self.span();
+            self.token_start = self.current_word;
+            let tokens = self.tokens.len();
+            if let Err(err) = self.tokenize_word() {
+                self.diagnostics.push(err);
+                self.tokens.truncate(tokens);
+                let broken = self.current_word.max(self.token_start).min(self.words.len() - 1);
+                let line = self.words[broken].span.line;
+                // the rest of the line is dropped as well, only its indentation stays for the blocks
+                while self.tokens.last().is_some_and(|token| token.span.line == line && !matches!(token.value, Token::OpeningTab(_))) {
+                    self.tokens.pop();
                 }
-                Word::La => {
-                    self.push(Token::La);
+                self.current_word = broken + 1;
+                while self.words.get(self.current_word).is_some_and(|word| word.span.line == line) {
                     self.consume();
                 }
-                Word::Wan | Word::Tu | Word::Luka => self.tokenize_nanpas(),
-                _ => todo!("{:#?}", word),
             }
         }
     }
-}
-
-#[derive(Debug)]
-enum Expression {
-    Unary(Box<UnaryExpression>),
-    Binary(Box<BinaryExpression>),
-}
-
-impl Expression {
-    fn get_type_name(&self, scope: &Scope) -> String {
-        match self {
-            Self::Unary(unary) => (*unary).get_type_name(scope).unwrap(),
-            Self::Binary(binary) => (*binary).get_type_name(scope),
-        }
-    }
-}
-
-#[derive(Debug, Clone, Copy)]
-enum BinaryExpressionType {
-    Add,
-    Multiply,
-    Subtract,
-    Divide,
-    GreaterThan,
-    Equals,
-    LessThan,
-}
-
-#[derive(Debug)]
-struct BinaryExpression {
-    lhs: Box<Expression>,
-    rhs: Box<Expression>,
-    kind: BinaryExpressionType,
-}
 
-impl BinaryExpression {
-    fn get_type_name(&self, scope: &Scope) -> String {
-        let lhstype = match &*self.lhs {
-            Expression::Binary(binary) => binary.get_type_name(scope),
-            Expression::Unary(unary) => unary
-                .get_type_name(scope)
-                .expect("binary expression hand has no type"),
-        };
-
-        let rhstype = match &*self.rhs {
-            Expression::Binary(binary) => binary.get_type_name(scope),
-            Expression::Unary(unary) => unary
-                .get_type_name(scope)
-                .expect("binary expression hand has no type"),
+    fn tokenize_word(&mut self) -> Result<(), Diagnostic> {
+        let word = match self.peek() {
+            None => return Ok(()),
+            Some(word) => word,
         };
 
-        if lhstype == rhstype {
-            return rhstype;
-        } else {
-            panic!(
-                "Expressions have incompatible types: {:#?} and {:#?}",
-                lhstype, rhstype
-            );
-        }
-    }
-}
-
-// Returning Expressions
-
+        match word {
+            Word::Name(_) => self.tokenize_name(),
+            Word::Number(_) => self.tokenize_number(),
+	    Word::StringLiteral(string) => {
+		self.push(Token::StringLiteral(string.to_string()));
+		self.consume();
+	    }
+            Word::Asen => {
+                self.push(Token::Asen);
+                self.consume();
+            }
+            Word::O => self.tokenize_o()?,
+            Word::Tenpo => self.tokenize_tenpo()?,
+            Word::Plus
+            | Word::Minus
+            | Word::ForwardSlash
+            | Word::Star
+            | Word::Percent
+            | Word::Ampersand
+            | Word::Pipe
+            | Word::Caret
+            | Word::ShiftLeft
+            | Word::ShiftRight
+            | Word::Equals
+            | Word::DoubleEquals
+            | Word::Unequals
+            | Word::LessThan
+            | Word::GreaterThan
+            | Word::LessThanEquals
+            | Word::GreaterThanEquals => self.tokenize_arithmetics(),
+            Word::Nanpa => {
+                self.push(Token::Nanpa);
+                self.consume();
+            }
+            Word::Linja => {
+                self.push(Token::Linja);
+                self.consume();
+            }
+            Word::Lili => {
+                self.push(Token::Lili);
+                self.consume();
+            }
+            Word::Suli => {
+                self.push(Token::Suli);
+                self.consume();
+            }
+            Word::Sitelen => {
+                self.push(Token::Sitelen);
+                self.consume();
+            }
+            // telo tu is the double precision one
+            Word::Telo => {
+                self.consume();
+                if self.expect(Word::Tu) {
+                    self.consume();
+                    self.push(Token::Telotu);
+                } else {
+                    self.push(Token::Telo);
+                }
+            }
+            Word::Signed => {
+                self.push(Token::Signed);
+                self.consume();
+            }
+            Word::Unsigned => {
+                self.push(Token::Unsigned);
+                self.consume();
+            }
+            Word::Period => {
+                self.push(Token::Period);
+                self.consume();
+            }
+            Word::Li => self.tokenize_li()?,
+            Word::Kepeken => self.tokenize_kepeken()?,
+            Word::En => {
+                self.push(Token::En);
+                self.consume();
+            }
+            Word::Anu => {
+                self.push(Token::Anu);
+                self.consume();
+            }
+            Word::Ala => {
+                self.push(Token::Ala);
+                self.consume();
+            }
+            Word::Lon => {
+                self.push(Token::Lon);
+                self.consume();
+            }
+            Word::Awen => {
+                self.push(Token::Awen);
+                self.consume();
+            }
+            Word::Lukin => {
+                self.push(Token::Lukin);
+                self.consume();
+            }
+            Word::E => {
+                self.push(Token::E);
+                self.consume();
+            }
+	    Word::A => {
+		self.push(Token::A);
+		self.consume();
+	    }
+            Word::OpeningTab(tabs) => {
+                self.push(Token::OpeningTab(*tabs));
+                self.consume();
+            }
+            Word::Pali => {
+                self.push(Token::Pali);
+                self.consume();
+            }
+            Word::OpenParenthesis => {
+                self.push(Token::OpenParenthesis);
+                self.consume();
+            }
+            Word::CloseParenthesis => {
+                self.push(Token::CloseParenthesis);
+                self.consume();
+            }
+            Word::La => {
+                self.push(Token::La);
+                self.consume();
+            }
+            Word::Ante => {
+                self.push(Token::Ante);
+                self.consume();
+            }
+            word if Self::is_number(word) => self.tokenize_nanpas()?,
+            _ => {
+                return Err(Diagnostic::error(
+                    "E0201",
+                    format!("unexpected word {:?}", word),
+                    &self.token_span,
+                ))
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+enum Expression {
+    Unary(Box<UnaryExpression>),
+    Binary(Box<BinaryExpression>),
+}
+
+impl Expression {
+    fn span(&self) -> &Span {
+        match self {
+            Self::Unary(unary) => unary.span(),
+            Self::Binary(binary) => binary.lhs.span(),
+        }
+    }
+
+    fn get_type_name(&self, scope: &Scope) -> Result<String, Diagnostic> {
+        match self {
+            Self::Unary(unary) => match unary.get_type_name(scope)? {
+                Some(type_name) => Ok(type_name),
+                None => Err(Diagnostic::error(
+                    "E0404",
+                    "this expression has no value",
+                    unary.span(),
+                )
+                .with_note("the function does not 'pana e' anything")),
+            },
+            Self::Binary(binary) => binary.get_type_name(scope),
+        }
+    }
+
+    // the value of an expression made only of literals, like 1 + 2 or -(1.5 * 2.0).
+    // with a scope, awen names count as their value too.
+    // anything that can not be worked out here, like a division by zero, is left to run time
+    fn evaluate(&self, scope: Option<&Scope>) -> Option<Constant> {
+        match self {
+            Self::Unary(unary) => match unary.as_ref() {
+                UnaryExpression::Nanpa(nanpa) => Some(Constant::Nanpa(nanpa.value)),
+                UnaryExpression::Telo(telo) => Some(Constant::Telo(telo.value)),
+                UnaryExpression::Lon(lon) => Some(Constant::Lon(lon.value)),
+                UnaryExpression::Nimi(nimi) => scope?.get_variable(&nimi.value, &nimi.span).ok()?.0.value,
+                UnaryExpression::Group(group) => group.expr.evaluate(scope),
+                UnaryExpression::Ala(ala) => match ala.expr.evaluate(scope)? {
+                    Constant::Lon(value) => Some(Constant::Lon(!value)),
+                    _ => None,
+                },
+                UnaryExpression::Negative(negative) => match negative.expr.evaluate(scope)?.as_number()? {
+                    Constant::Nanpa(value) => value.checked_neg().map(Constant::Nanpa),
+                    Constant::Telo(value) => Some(Constant::Telo(-value)),
+                    Constant::Lon(_) => None,
+                },
+                _ => None,
+            },
+            Self::Binary(binary) => binary.evaluate(scope),
+        }
+    }
+
+    // only the word ala can be used as zero, not something that turns out false
+    fn is_ala(&self) -> bool {
+        match self {
+            Self::Unary(unary) => match unary.as_ref() {
+                UnaryExpression::Lon(lon) => !lon.value,
+                UnaryExpression::Group(group) => group.expr.is_ala(),
+                _ => false,
+            },
+            Self::Binary(_) => false,
+        }
+    }
+
+    // number literals and constants made of them take whatever number type they are used as, 1.5 only a telo type,
+    // other numbers can be used as a wider type, everything else has to have exactly that type
+    fn fits_type(&self, type_name: &str, scope: &Scope) -> Result<bool, Diagnostic> {
+        let expected = scope.get_type(type_name, self.span())?;
+        match self.evaluate(None) {
+            Some(Constant::Nanpa(_)) => return Ok(matches!(expected.kind, TypeKind::Integer | TypeKind::Float)),
+            Some(Constant::Telo(_)) => return Ok(expected.kind == TypeKind::Float),
+            Some(Constant::Lon(_)) => {
+                return Ok(expected.kind == TypeKind::Lon
+                    || (self.is_ala() && matches!(expected.kind, TypeKind::Integer | TypeKind::Float)));
+            }
+            None => {}
+        }
+        let actual = self.get_type_name(scope)?;
+        Ok(actual == type_name || scope.get_type(&actual, self.span())?.widens_to(&expected))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Constant {
+    Nanpa(i128),
+    Telo(f64),
+    // ala is false, and also the number zero
+    Lon(bool),
+}
+
+impl Constant {
+    // the constant as a number, if it is one
+    fn as_number(self) -> Option<Constant> {
+        match self {
+            Self::Lon(false) => Some(Self::Nanpa(0)),
+            Self::Lon(true) => None,
+            constant => Some(constant),
+        }
+    }
+}
+
+impl fmt::Display for Constant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Nanpa(value) => write!(f, "{value}"),
+            Self::Telo(value) => write!(f, "{value}"),
+            Self::Lon(true) => write!(f, "lon"),
+            Self::Lon(false) => write!(f, "ala"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum BinaryExpressionType {
+    Add,
+    Multiply,
+    Subtract,
+    Divide,
+    Modulo,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+    GreaterThan,
+    Equals,
+    Unequals,
+    LessThan,
+    LessEquals,
+    GreaterEquals,
+    And,
+    Or,
+}
+
+#[derive(Debug)]
+struct BinaryExpression {
+    lhs: Box<Expression>,
+    rhs: Box<Expression>,
+    kind: BinaryExpressionType,
+    span: Span,
+}
+
+impl BinaryExpressionType {
+    // what a comparison gives for operands ordered like this, none when they can not be ordered (NaN)
+    fn compare(self, ordering: Option<Ordering>) -> Option<bool> {
+        Some(match self {
+            Self::Equals => ordering == Some(Ordering::Equal),
+            Self::Unequals => ordering != Some(Ordering::Equal),
+            Self::LessThan => ordering == Some(Ordering::Less),
+            Self::LessEquals => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+            Self::GreaterThan => ordering == Some(Ordering::Greater),
+            Self::GreaterEquals => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+            _ => return None,
+        })
+    }
+}
+
+impl BinaryExpression {
+    fn evaluate(&self, scope: Option<&Scope>) -> Option<Constant> {
+        let kind = self.kind;
+        let (lhs, rhs) = (self.lhs.evaluate(scope)?, self.rhs.evaluate(scope)?);
+        if let (Constant::Lon(lhs), Constant::Lon(rhs)) = (lhs, rhs) {
+            return match kind {
+                BinaryExpressionType::And => Some(Constant::Lon(lhs && rhs)),
+                BinaryExpressionType::Or => Some(Constant::Lon(lhs || rhs)),
+                BinaryExpressionType::Equals => Some(Constant::Lon(lhs == rhs)),
+                BinaryExpressionType::Unequals => Some(Constant::Lon(lhs != rhs)),
+                _ => None,
+            };
+        }
+        match (lhs.as_number()?, rhs.as_number()?) {
+            (Constant::Nanpa(lhs), Constant::Nanpa(rhs)) => {
+                if let Some(value) = kind.compare(Some(lhs.cmp(&rhs))) {
+                    return Some(Constant::Lon(value));
+                }
+                let value = match kind {
+                    BinaryExpressionType::Add => lhs.checked_add(rhs),
+                    BinaryExpressionType::Subtract => lhs.checked_sub(rhs),
+                    BinaryExpressionType::Multiply => lhs.checked_mul(rhs),
+                    BinaryExpressionType::Divide => lhs.checked_div(rhs),
+                    BinaryExpressionType::Modulo => lhs.checked_rem(rhs),
+                    BinaryExpressionType::BitAnd => Some(lhs & rhs),
+                    BinaryExpressionType::BitOr => Some(lhs | rhs),
+                    BinaryExpressionType::BitXor => Some(lhs ^ rhs),
+                    BinaryExpressionType::ShiftLeft => lhs.checked_shl(u32::try_from(rhs).ok()?),
+                    BinaryExpressionType::ShiftRight => lhs.checked_shr(u32::try_from(rhs).ok()?),
+                    _ => None,
+                };
+                value.map(Constant::Nanpa)
+            }
+            (lhs, rhs) => {
+                let as_telo = |constant| match constant {
+                    Constant::Nanpa(value) => value as f64,
+                    Constant::Telo(value) => value,
+                    Constant::Lon(_) => unreachable!("lon is not a number"),
+                };
+                let (lhs, rhs) = (as_telo(lhs), as_telo(rhs));
+                if let Some(value) = kind.compare(lhs.partial_cmp(&rhs)) {
+                    return Some(Constant::Lon(value));
+                }
+                let value = match kind {
+                    BinaryExpressionType::Add => lhs + rhs,
+                    BinaryExpressionType::Subtract => lhs - rhs,
+                    BinaryExpressionType::Multiply => lhs * rhs,
+                    BinaryExpressionType::Divide => lhs / rhs,
+                    _ => return None,
+                };
+                Some(Constant::Telo(value))
+            }
+        }
+    }
+
+    fn get_type_name(&self, scope: &Scope) -> Result<String, Diagnostic> {
+        let operand_type_name = self.get_operand_type_name(scope)?;
+        match self.kind {
+            BinaryExpressionType::Equals
+            | BinaryExpressionType::Unequals
+            | BinaryExpressionType::LessThan
+            | BinaryExpressionType::GreaterThan
+            | BinaryExpressionType::LessEquals
+            | BinaryExpressionType::GreaterEquals
+            | BinaryExpressionType::And
+            | BinaryExpressionType::Or => Ok("lon".to_string()),
+            _ => Ok(operand_type_name),
+        }
+    }
+
+    // the type both sides are calculated in
+    fn get_operand_type_name(&self, scope: &Scope) -> Result<String, Diagnostic> {
+        let lhstype = self.lhs.get_type_name(scope)?;
+        let rhstype = self.rhs.get_type_name(scope)?;
+
+        if self.rhs.fits_type(&lhstype, scope)? {
+            Ok(lhstype)
+        } else if self.lhs.fits_type(&rhstype, scope)? {
+            Ok(rhstype)
+        } else {
+            Err(Diagnostic::error(
+                "E0404",
+                format!("Expressions have incompatible types: {lhstype} and {rhstype}"),
+                &self.span,
+            ))
+        }
+    }
+}
+
+// Returning Expressions
+
+// ala 'expr'
+#[derive(Debug)]
+struct AlaExpression {
+    expr: Expression,
+    span: Span,
+}
+
+// - 'expr'
+#[derive(Debug)]
+struct NegativeExpression {
+    expr: Expression,
+    span: Span,
+}
+
+// ( 'expr' ) inside an expression, a ( that starts a statement is a Parenthesis block
+#[derive(Debug)]
+struct GroupExpression {
+    expr: Expression,
+    span: Span,
+}
+
 #[derive(Debug)]
 struct NimiExpression {
     value: String,
+    span: Span,
 }
 
 #[derive(Debug)]
 struct LinjaExpression {
     value: String,
+    span: Span,
 }
 
 #[derive(Debug)]
 struct NanpaExpression {
-    value: isize,
+    value: i128,
+    span: Span,
+}
+
+#[derive(Debug)]
+struct TeloExpression {
+    value: f64,
+    span: Span,
+}
+
+// lon, or ala on its own
+#[derive(Debug)]
+struct LonExpression {
+    value: bool,
+    span: Span,
 }
 
 #[derive(Debug)]
+#[allow(unused)]
 enum UnaryExpression {
     Nanpa(Box<NanpaExpression>),
+    Telo(Box<TeloExpression>),
+    Lon(Box<LonExpression>),
     Nimi(Box<NimiExpression>),
     O(Box<OExpression>),
     Linja(Box<LinjaExpression>),
+    Ala(Box<AlaExpression>),
+    Negative(Box<NegativeExpression>),
+    Group(Box<GroupExpression>),
 }
 
 impl UnaryExpression {
-    fn get_type_name(&self, scope: &Scope) -> Option<String> {
+    fn span(&self) -> &Span {
         match self {
+            Self::Nanpa(nanpa) => &nanpa.span,
+            Self::Telo(telo) => &telo.span,
+            Self::Lon(lon) => &lon.span,
+            Self::Nimi(nimi) => &nimi.span,
+            Self::O(o) => &o.span,
+            Self::Linja(linja) => &linja.span,
+            Self::Ala(ala) => &ala.span,
+            Self::Negative(negative) => &negative.span,
+            Self::Group(group) => &group.span,
+        }
+    }
+
+    fn get_type_name(&self, scope: &Scope) -> Result<Option<String>, Diagnostic> {
+        Ok(match self {
             Self::Nanpa(_) => Some("nanpa".to_string()),
+            Self::Telo(_) => Some("telo tu".to_string()),
+            Self::Lon(_) => Some("lon".to_string()),
             Self::Linja(_) => Some("linja".to_string()),
-            Self::Nimi(nimi) => Some(scope.get_variable(&nimi.value).unwrap().0.type_name.clone()),
-            Self::O(o) => Some(scope.get_function(&o.nimi.value).unwrap().return_type.clone()?),
-        }
+            Self::Nimi(nimi) => Some(scope.get_variable(&nimi.value, &nimi.span)?.0.type_name.clone()),
+            Self::O(o) => scope.get_function(&o.nimi.value, &o.nimi.span)?.return_type.clone(),
+            Self::Ala(_) => Some("lon".to_string()),
+            Self::Negative(negative) => Some(negative.expr.get_type_name(scope)?),
+            Self::Group(group) => Some(group.expr.get_type_name(scope)?),
+        })
     }
 }
 
@@ -791,74 +1634,112 @@ impl UnaryExpression {
 // Non-Returning
 struct AsenpeliStatement {
     value: String,
+    span: Span,
 }
 #[derive(Debug)]
+#[allow(unused)]
 struct Parenthesis {
     nodes: Vec<Node>,
+    span: Span,
 }
 
 #[derive(Debug)]
+#[allow(unused)]
 struct OtawaStatement {
     expr: Box<Expression>,
+    span: Span,
 }
 
 #[derive(Debug)]
+#[allow(unused)]
 struct KepekenStatement {
-    nimi: NimiExpression
+    nimi: NimiExpression,
+    span: Span,
 }
 
 #[derive(Debug)]
+#[allow(unused)]
 struct OWekaStatement {
     expr: Option<Box<Expression>>,
+    // the return added at o pini of a pali that does not end in one
+    implicit: bool,
+    span: Span,
 }
 
 #[derive(Debug)]
+#[allow(unused)]
 struct OSinStatement {
     var_type: String,
     name: NimiExpression,
     expr: Option<Box<Expression>>,
+    // declared with awen, so it can not be changed
+    constant: bool,
+    // declared with lukin, so other files can use it
+    public: bool,
+    span: Span,
 }
 
 #[derive(Debug)]
+#[allow(unused)]
 struct LiKamaSamaStatement {
     nimi: NimiExpression,
     expression: Box<Expression>,
+    span: Span,
 }
 
 #[derive(Debug)]
+#[allow(unused)]
 struct PaliStatement {
     nimi: NimiExpression,
     params: Vec<(String, NimiExpression)>,
     nodes: Vec<Node>,
     retval: Option<String>,
+    span: Span,
 }
 
 #[derive(Debug)]
+#[allow(unused)]
 struct PaliDeclaration {
     nimi: NimiExpression,
     params: Vec<(String, NimiExpression)>,
-    retval: Option<String>
+    retval: Option<String>,
+    span: Span,
 }
 
 #[derive(Debug)]
 struct OExpression {
     nimi: NimiExpression,
     params: Vec<Expression>,
+    span: Span,
 }
 
 #[derive(Debug)]
+#[allow(unused)]
 struct TenpoStatement {
     expr: Box<Expression>,
     nodes: Vec<Node>,
+    ante: Option<AnteStatement>,
+    span: Span,
+}
+
+#[derive(Debug)]
+enum AnteStatement {
+    // ante tenpo pi 'expr' la
+    Tenpo(Box<TenpoStatement>),
+    // ante la
+    La(Vec<Node>),
 }
 
 #[derive(Debug)]
+#[allow(unused)]
 struct TenpoAleStatement {
     expr: Expression,
     nodes: Vec<Node>,
+    span: Span,
 }
 
 #[derive(Debug)]
+#[allow(unused)]
 enum Node {
     Expression(Box<Expression>),
     Asenpeli(Box<AsenpeliStatement>),
@@ -877,15 +1758,28 @@ enum Node {
 }
 
 struct Parser {
-    tokens: Vec<Token>,
+    tokens: Vec<Spanned<Token>>,
     current_token: usize,
     nodes: Vec<Node>,
+    diagnostics: Vec<Diagnostic>,
+    // how many statement ( blocks the parser is in
+    paren_blocks: usize,
     debug_mode: bool,
 }
 
 impl Parser {
     fn peek(&self) -> Option<&Token> {
-        self.tokens.get(self.current_token)
+        self.tokens.get(self.current_token).map(|token| &token.value)
+    }
+
+    // span of the current token, or of the last one once the tokens run out
+    fn span(&self) -> Span {
+        self.tokens
+            .get(self.current_token)
+            .or(self.tokens.last())
+            .expect("no tokens to take a span from")
+            .span
+            .clone()
     }
 
     fn consume(&mut self) {
@@ -895,38 +1789,174 @@ impl Parser {
     fn expect(&self, expected: Token) -> bool {
         match self.peek() {
             None => false,
-            Some(token) => {
-                if std::mem::discriminant(token) == std::mem::discriminant(&expected) {
-                    true
-                } else {
-                    false
+            Some(token) => std::mem::discriminant(token) == std::mem::discriminant(&expected),
+        }
+    }
+
+    fn unexpected_end(&self) -> Diagnostic {
+        Diagnostic::error("E0301", "Unexpected end of file", &self.span())
+    }
+
+    // whether the current token is the first one on its line, which is where statements start
+    fn at_line_start(&self) -> bool {
+        match (self.current_token.checked_sub(1).and_then(|previous| self.tokens.get(previous)), self.tokens.get(self.current_token)) {
+            (Some(previous), Some(token)) => token.span.line > previous.span.line,
+            _ => true,
+        }
+    }
+
+    // Skips the rest of a statement that started at `start` and failed to
+    // parse. Blocks the statement already opened are skipped up to their
+    // 'o pini', then it stops after a '.', at the start of the next line or before
+    // anything that can only start a new statement: 'o pini', 'ante', 'pali', 'o sin e', 'tenpo pi', ...
+    fn synchronize(&mut self, start: usize) {
+        // only blocks are counted, parentheses can also be half of a broken expression
+        let mut depth = 0usize;
+        for token in &self.tokens[start..self.current_token] {
+            match token.value {
+                Token::TenpoPi | Token::TenpoAlePi | Token::LiPaliENi => depth += 1,
+                Token::OPini => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+
+        // a statement that failed on its first token has to skip at least that
+        if self.current_token == start {
+            self.consume();
+        }
+
+        while let Some(token) = self.peek() {
+            if depth == 0 && self.at_line_start() {
+                return;
+            }
+            match token {
+                Token::Pali => return,
+                Token::OPini if depth > 0 => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.consume();
+                        return;
+                    }
+                }
+                Token::Period if depth == 0 => {
+                    self.consume();
+                    return;
+                }
+                // closes the ( block the statement is in
+                Token::CloseParenthesis if depth == 0 && self.paren_blocks > 0 => return,
+                Token::OPini
+                | Token::Ante
+                | Token::OSin
+                | Token::OTawa
+                | Token::OWeka
+                | Token::O
+                | Token::TenpoPi
+                | Token::TenpoAlePi
+                | Token::Kepeken
+                | Token::Asen
+                    if depth == 0 =>
+                {
+                    return
+                }
+                Token::TenpoPi | Token::TenpoAlePi | Token::LiPaliENi => depth += 1,
+                _ => {}
+            }
+
+            self.consume();
+        }
+    }
+
+    // parses statements up to the 'o pini' or 'ante' closing the block, without consuming it
+    fn parse_block(&mut self, nodes: &mut Vec<Node>, opener: &Span) -> Result<(), Diagnostic> {
+        loop {
+            match self.peek() {
+                Some(Token::OPini) | Some(Token::Ante) => return Ok(()),
+                None | Some(Token::Pali) => {
+                    return Err(Diagnostic::error("E0312", "missing 'o pini'", &self.span())
+                        .with_note(format!("the block opened at {opener} is never closed")));
+                }
+                Some(_) => {}
+            }
+
+            let start = self.current_token;
+            match self.parse_statement() {
+                Ok(node) => nodes.push(node),
+                Err(err) => {
+                    self.diagnostics.push(err);
+                    self.synchronize(start);
                 }
             }
         }
     }
 
-    fn parse_nanpa_expression(&mut self) -> Result<NanpaExpression, String> {
+    // 1_000, 0xff, 0b1010, 0o17. whether the value fits is checked against its type later
+    fn parse_number_literal(number: &str) -> Option<i128> {
+        let digits = number.replace('_', "");
+        let (radix, digits) = match digits.get(..2) {
+            Some("0x") => (16, &digits[2..]),
+            Some("0b") => (2, &digits[2..]),
+            Some("0o") => (8, &digits[2..]),
+            _ => (10, &digits[..]),
+        };
+        if digits.is_empty() {
+            return None;
+        }
+        i128::from_str_radix(digits, radix).ok()
+    }
+
+    fn parse_nanpa_expression(&mut self) -> Result<NanpaExpression, Diagnostic> {
+        let span = self.span();
         let token = match self.peek() {
-            None => panic!(),
+            None => return Err(self.unexpected_end()),
             Some(token) => token,
         };
 
         match token {
             Token::Number(number) => {
-                let result = Ok(NanpaExpression {
-                    value: number.parse().expect("not a valid number"),
-                });
+                let value = match Self::parse_number_literal(number) {
+                    Some(value) => value,
+                    None => {
+                        return Err(Diagnostic::error(
+                            "E0303",
+                            format!("{number} is not a valid number"),
+                            &span,
+                        )
+                        .with_note("numbers are decimal, or hex, binary and octal with 0x, 0b and 0o. '_' can separate digits"))
+                    }
+                };
                 self.consume();
 
-                result
+                Ok(NanpaExpression { value, span })
             }
-            _ => Err("not a number".to_string()),
+            _ => Err(Diagnostic::error("E0303", "not a number", &span)),
         }
     }
 
-    fn parse_nimi_expression(&mut self) -> Result<NimiExpression, String> {
+    // 1.5, whether it is a telo or a telo tu depends on where it is used
+    fn parse_telo_expression(&mut self) -> Result<TeloExpression, Diagnostic> {
+        let span = self.span();
+        let number = match self.peek() {
+            None => return Err(self.unexpected_end()),
+            Some(Token::Number(number)) => number.replace('_', ""),
+            Some(_) => return Err(Diagnostic::error("E0303", "not a number", &span)),
+        };
+
+        let value = match number.parse::<f64>() {
+            Ok(value) => value,
+            Err(_) => {
+                return Err(Diagnostic::error("E0303", format!("{number} is not a valid number"), &span)
+                    .with_note("a telo is written with digits on both sides of the period, like 1.5"));
+            }
+        };
+        self.consume();
+
+        Ok(TeloExpression { value, span })
+    }
+
+    fn parse_nimi_expression(&mut self) -> Result<NimiExpression, Diagnostic> {
+        let span = self.span();
         let token = match self.peek() {
-            None => panic!(),
+            None => return Err(self.unexpected_end()),
             Some(token) => token,
         };
 
@@ -934,35 +1964,75 @@ impl Parser {
             Token::Name(name) => {
                 let result = Ok(NimiExpression {
                     value: name.clone(),
+                    span,
                 });
                 self.consume();
 
                 result
             }
-            _ => Err("not a name".to_string()),
+            _ => Err(Diagnostic::error("E0302", "not a name", &span)),
         }
     }
 
-    fn parse_kepeken(&mut self) -> Result<KepekenStatement, String> {
-	if !self.expect(Token::StringLiteral("".to_string())) {
-	    return Err("not a kepeken statement".to_string());
+    // kepeken "'module'"
+    fn parse_kepeken(&mut self) -> Result<KepekenStatement, Diagnostic> {
+	let span = self.span();
+	if !self.expect(Token::Kepeken) {
+	    return Err(Diagnostic::error("E0311", "not a kepeken statement", &span));
 	}
+	self.consume();
 
-	let nimi = self.parse_nimi_expression()?;
+	let nimi = match self.peek() {
+	    None => return Err(self.unexpected_end()),
+	    Some(Token::StringLiteral(module)) => NimiExpression {
+		value: module.clone(),
+		span: self.span(),
+	    },
+	    Some(_) => {
+		return Err(Diagnostic::error("E0311", "kepeken needs the name of a module", &self.span())
+		    .with_note("write it as kepeken \"pu\""));
+	    }
+	};
+	self.consume();
 
-	return Ok(KepekenStatement{
-	    nimi
+	Ok(KepekenStatement{
+	    nimi,
+	    span,
 	})
     }
-    
-    fn parse_unary_expression(&mut self) -> Result<UnaryExpression, String> {
-        let token = match self.peek() {
-            None => panic!(),
-            Some(token) => token,
-        };
 
-        if matches!(token, Token::Number(_)) {
-            return Ok(UnaryExpression::Nanpa(Box::new(
+    // asen "'fasm'"
+    fn parse_asen(&mut self) -> Result<AsenpeliStatement, Diagnostic> {
+        if !self.expect(Token::Asen) {
+            return Err(Diagnostic::error("E0309", "not an asen statement", &self.span()));
+        }
+        self.consume();
+
+        let span = self.span();
+        let value = match self.peek() {
+            None => return Err(self.unexpected_end()),
+            Some(Token::StringLiteral(value)) => value.clone(),
+            Some(_) => {
+                return Err(Diagnostic::error("E0314", "asen needs a string of assembly", &span)
+                    .with_note("write it as asen \"mov rax, {X}\""));
+            }
+        };
+        self.consume();
+
+        Ok(AsenpeliStatement { value, span })
+    }
+
+    fn parse_unary_expression(&mut self) -> Result<UnaryExpression, Diagnostic> {
+        let span = self.span();
+        let token = match self.peek() {
+            None => return Err(self.unexpected_end()),
+            Some(token) => token,
+        };
+
+        if matches!(token, Token::Number(number) if number.contains('.')) {
+            return Ok(UnaryExpression::Telo(Box::new(self.parse_telo_expression()?)));
+        } else if matches!(token, Token::Number(_)) {
+            return Ok(UnaryExpression::Nanpa(Box::new(
                 self.parse_nanpa_expression()?,
             )));
         } else if matches!(token, Token::Name(_)) {
@@ -971,18 +2041,62 @@ impl Parser {
             )));
         } else if matches!(token, Token::O) {
             return Ok(UnaryExpression::O(Box::new(self.parse_o()?)));
-        } else if matches!(token, Token::StringLiteral(_)) {
-            todo!("parse linja expression");
+        } else if matches!(token, Token::Lon) {
+            self.consume();
+            return Ok(UnaryExpression::Lon(Box::new(LonExpression { value: true, span })));
+        } else if matches!(token, Token::Ala) {
+            self.consume();
+            // ala on its own is false, or the number zero where a number is expected.
+            // at the end of a line it is always on its own
+            if self.at_line_start() || !matches!(
+                self.peek(),
+                Some(Token::Number(_) | Token::Name(_) | Token::O | Token::Ala | Token::Lon | Token::OpenParenthesis | Token::StringLiteral(_))
+            ) {
+                return Ok(UnaryExpression::Lon(Box::new(LonExpression { value: false, span })));
+            }
+            // ala binds looser than comparisons: ala X = 1 is ala (X = 1)
+            let expr = self.parse_expression(Precedence::Comparing)?;
+            return Ok(UnaryExpression::Ala(Box::new(AlaExpression { expr, span })));
+        } else if matches!(token, Token::OpenParenthesis) {
+            self.consume();
+            let expr = self.parse_expression(Precedence::Undefined)?;
+            if !self.expect(Token::CloseParenthesis) {
+                return Err(Diagnostic::error("E0315", "expected ')'", &self.span())
+                    .with_note(format!("to close the '(' at {span}")));
+            }
+            self.consume();
+            return Ok(UnaryExpression::Group(Box::new(GroupExpression { expr, span })));
+        } else if matches!(token, Token::Minus) {
+            self.consume();
+            let expr = self.parse_expression(Precedence::Unary)?;
+            // a negative literal stays a literal, so the smallest nanpa can be written
+            return Ok(match expr {
+                Expression::Unary(unary) => match *unary {
+                    UnaryExpression::Nanpa(nanpa) => {
+                        UnaryExpression::Nanpa(Box::new(NanpaExpression { value: -nanpa.value, span }))
+                    }
+                    UnaryExpression::Telo(telo) => {
+                        UnaryExpression::Telo(Box::new(TeloExpression { value: -telo.value, span }))
+                    }
+                    unary => UnaryExpression::Negative(Box::new(NegativeExpression {
+                        expr: Expression::Unary(Box::new(unary)),
+                        span,
+                    })),
+                },
+                expr => UnaryExpression::Negative(Box::new(NegativeExpression { expr, span })),
+            });
+        } else if let Token::StringLiteral(value) = token {
+            let value = value.clone();
+            self.consume();
+            return Ok(UnaryExpression::Linja(Box::new(LinjaExpression { value, span })));
         }
 
-        Err("Not an unary expression".to_string())
+        Err(Diagnostic::error("E0304", "Not an unary expression", &span)
+            .with_note("expected a number, a string, a name, an 'o' call, lon, '(', '-' or 'ala'"))
     }
 
-    fn parse_expression(&mut self, min_precedence: Precedence) -> Result<Expression, String> {
-        let lhs_unary = match self.parse_unary_expression() {
-            Err(err) => return Err(err),
-            Ok(expr) => expr,
-        };
+    fn parse_expression(&mut self, min_precedence: Precedence) -> Result<Expression, Diagnostic> {
+        let lhs_unary = self.parse_unary_expression()?;
 
         let mut lhs_expr = Expression::Unary(Box::new(lhs_unary));
 
@@ -992,38 +2106,35 @@ impl Parser {
                 Some(token) => token,
             };
 
-            let binary_type: BinaryExpressionType = match token {
-                Token::Plus => BinaryExpressionType::Add,
-                Token::Minus => BinaryExpressionType::Subtract,
-                Token::Star => BinaryExpressionType::Multiply,
-                Token::ForwardSlash => BinaryExpressionType::Divide,
-                Token::GreaterThan => BinaryExpressionType::GreaterThan,
-                Token::LessThan => BinaryExpressionType::LessThan,
-                Token::Equals => BinaryExpressionType::Equals,
-                _ => break,
+            let operator = match get_binary_operator(token) {
+                None => break,
+                Some(operator) => operator,
             };
 
-            let current_precedence = get_precedence(binary_type);
-
-            if current_precedence < min_precedence {
+            // an expression ends with its line
+            if operator.precedence < min_precedence || self.at_line_start() {
                 break;
             }
 
+            let span = self.span();
             self.consume();
+            if self.peek().is_none() || self.at_line_start() {
+                return Err(Diagnostic::error("E0316", format!("{:?} needs a value after it", operator.kind), &span)
+                    .with_note("the value has to be on the same line"));
+            }
 
-            let rhs_expr = match self.parse_expression(current_precedence.next()) {
-                Err(err) => return Err(err),
-                Ok(expr) => expr,
+            // the right side only takes operators that bind tighter, or equally
+            // tight ones when they group to the right
+            let rhs_expr = match operator.associativity {
+                Associativity::Left => self.parse_expression(operator.precedence.next())?,
+                Associativity::Right => self.parse_expression(operator.precedence)?,
             };
 
-            let lhs_expr2 = lhs_expr;
-
-            // get variable type
-            let vartype: String;
             let binary_expression = BinaryExpression {
-                kind: binary_type,
-                lhs: Box::new(lhs_expr2),
+                kind: operator.kind,
+                lhs: Box::new(lhs_expr),
                 rhs: Box::new(rhs_expr),
+                span,
             };
 
             lhs_expr = Expression::Binary(Box::new(binary_expression));
@@ -1032,60 +2143,110 @@ impl Parser {
         Ok(lhs_expr)
     }
 
-    fn parse_otawa(&mut self) -> Result<OtawaStatement, String> {
-        let token = match self.peek() {
-            None => panic!(),
-            Some(token) => token,
-        };
-        if !matches!(token, Token::OTawa) {
-            return Err(String::from("Not an otawa statement"));
+    fn parse_otawa(&mut self) -> Result<OtawaStatement, Diagnostic> {
+        let span = self.span();
+        if !self.expect(Token::OTawa) {
+            return Err(Diagnostic::error("E0309", "Not an otawa statement", &span));
         };
 
         self.consume();
 
-        let expr: Expression = match self.parse_expression(Precedence::Undefined) {
-            Err(err) => return Err(err),
-            Ok(expr) => expr,
-        };
+        let expr: Expression = self.parse_expression(Precedence::Undefined)?;
 
         Ok(OtawaStatement {
             expr: Box::new(expr),
+            span,
         })
     }
 
-    // tenpo pi 'expr' la
-    fn parse_tenpo(&mut self) -> Result<TenpoStatement, String> {
+    fn parse_o_pini(&mut self, opener: &Span) -> Result<(), Diagnostic> {
+        if self.expect(Token::Ante) {
+            return Err(Diagnostic::error("E0313", "'ante' does not belong to a 'tenpo pi' block", &self.span())
+                .with_note(format!("the block opened at {opener} has to end with 'o pini'")));
+        }
+        self.consume();
+        Ok(())
+    }
+
+    // tenpo pi 'expr' la ... [ante tenpo pi 'expr' la ...] [ante la ...] o pini
+    fn parse_tenpo(&mut self) -> Result<TenpoStatement, Diagnostic> {
+        let span = self.span();
         if !self.expect(Token::TenpoPi) {
-            return Err("not a tenpo statement".to_string());
+            return Err(Diagnostic::error("E0309", "not a tenpo statement", &span));
         }
         self.consume();
 
         let expr = self.parse_expression(Precedence::Undefined)?;
 
         if !self.expect(Token::La) {
-            return Err("no la in tenpo statement".to_string());
+            return Err(Diagnostic::error("E0305", "no la in tenpo statement", &self.span())
+                .with_note("a condition is written as 'tenpo pi <expression> la'"));
         }
         self.consume();
 
         let mut nodes: Vec<Node> = Vec::new();
 
-        loop {
-            nodes.push(self.parse_statement());
-            if self.expect(Token::OPini) {
+        self.parse_block(&mut nodes, &span)?;
+
+        // the whole chain shares the 'o pini' of its last branch
+        let mut ante = None;
+        if self.expect(Token::Ante) {
+            let ante_span = self.span();
+            self.consume();
+
+            if self.expect(Token::TenpoPi) {
+                ante = Some(AnteStatement::Tenpo(Box::new(self.parse_tenpo()?)));
+            } else if self.expect(Token::La) {
                 self.consume();
-                break;
+                let mut ante_nodes: Vec<Node> = Vec::new();
+                self.parse_block(&mut ante_nodes, &ante_span)?;
+                self.parse_o_pini(&ante_span)?;
+                ante = Some(AnteStatement::La(ante_nodes));
+            } else {
+                return Err(Diagnostic::error("E0305", "no la in ante statement", &self.span())
+                    .with_note("write 'ante la' or 'ante tenpo pi <expression> la'"));
             }
+        } else {
+            self.consume();
         }
+
         Ok(TenpoStatement {
             expr: Box::new(expr),
             nodes,
+            ante,
+            span,
         })
     }
 
+    // tenpo ale pi 'expr' la
+    fn parse_tenpo_ale(&mut self) -> Result<TenpoAleStatement, Diagnostic> {
+        let span = self.span();
+        if !self.expect(Token::TenpoAlePi) {
+            return Err(Diagnostic::error("E0309", "not a tenpo ale statement", &span));
+        }
+        self.consume();
+
+        let expr = self.parse_expression(Precedence::Undefined)?;
+
+        if !self.expect(Token::La) {
+            return Err(Diagnostic::error("E0305", "no la in tenpo ale statement", &self.span())
+                .with_note("a loop is written as 'tenpo ale pi <expression> la'"));
+        }
+        self.consume();
+
+        let mut nodes: Vec<Node> = Vec::new();
+
+        self.parse_block(&mut nodes, &span)?;
+        self.parse_o_pini(&span)?;
+
+        Ok(TenpoAleStatement { expr, nodes, span })
+    }
+
     // o 'name' e 'name' e 'name'.
-    fn parse_o(&mut self) -> Result<OExpression, String> {
+    fn parse_o(&mut self) -> Result<OExpression, Diagnostic> {
+        let span = self.span();
         if !self.expect(Token::O) {
-            return Err("not an o expression".to_string());
+            return Err(Diagnostic::error("E0309", "not an o expression", &span));
         };
         self.consume();
 
@@ -1110,16 +2271,18 @@ impl Parser {
 
 	// a!
 	if !self.expect(Token::A) {
-	    return Err("not happy enough!".to_string());
+	    return Err(Diagnostic::error("E0306", "not happy enough!", &self.span())
+		.with_note("every 'o' call has to end with 'a'"));
 	}
 	self.consume();
 
-        Ok(OExpression { nimi, params })
+        Ok(OExpression { nimi, params, span })
     }
 
-    fn parse_o_weka(&mut self) -> Result<OWekaStatement, String> {
+    fn parse_o_weka(&mut self) -> Result<OWekaStatement, Diagnostic> {
+        let span = self.span();
         if !self.expect(Token::OWeka) {
-            return Err("not an o weka statement".to_string());
+            return Err(Diagnostic::error("E0309", "not an o weka statement", &span));
         };
         self.consume();
         let mut expr = None;
@@ -1128,17 +2291,18 @@ impl Parser {
             expr = Some(Box::new(self.parse_expression(Precedence::Undefined)?));
         };
 
-        Ok(OWekaStatement { expr })
+        Ok(OWekaStatement { expr, implicit: false, span })
     }
 
     // pali 'name' li kepeken 'args' li pali e ni:
-    fn parse_pali(&mut self) -> Result<(Option<PaliStatement>, Option<PaliDeclaration>), String> {
+    fn parse_pali(&mut self) -> Result<(Option<PaliStatement>, Option<PaliDeclaration>), Diagnostic> {
+        let span = self.span();
         if !self.expect(Token::Pali) {
-            return Err("not a pali statement".to_string());
+            return Err(Diagnostic::error("E0309", "not a pali statement", &span));
         }
         self.consume();
 
-        let mut nimi = self.parse_nimi_expression()?;
+        let nimi = self.parse_nimi_expression()?;
 
         let mut params: Vec<(String, NimiExpression)> = Vec::new();
         let mut has_params = false;
@@ -1153,6 +2317,8 @@ impl Parser {
                 self.consume();
 
                 loop {
+                    // parameters are not expressions, so here en is always
+                    // the separator and never a logical and
                     params.push((self.parse_type()?, self.parse_nimi_expression()?));
 
                     if !self.expect(Token::En) {
@@ -1173,6 +2339,7 @@ impl Parser {
 		     nimi,
 		     params,
 		     retval,
+		     span,
 		 })
 		)
 	    );
@@ -1181,19 +2348,16 @@ impl Parser {
 
         let mut nodes: Vec<Node> = Vec::new();
 
-        loop {
-            if self.expect(Token::OPini) {
-                self.consume();
-                break;
-            }
-
-            let node: Node = self.parse_statement();
-            nodes.push(node);
-        }
+        self.parse_block(&mut nodes, &span)?;
+        self.parse_o_pini(&span)?;
 
-        let oweka = Node::OWeka(Box::new(OWekaStatement { expr: None }));
+        let oweka = Node::OWeka(Box::new(OWekaStatement {
+            expr: None,
+            implicit: true,
+            span: self.tokens[self.current_token - 1].span.clone(),
+        }));
         if !nodes.iter().any(|node| {
-            return std::mem::discriminant(node) == std::mem::discriminant(&oweka);
+            std::mem::discriminant(node) == std::mem::discriminant(&oweka)
         }) {
             nodes.push(oweka);
         }
@@ -1203,59 +2367,101 @@ impl Parser {
             params,
             nodes,
             retval,
+            span,
         }), None))
     }
 
-    fn parse_type(&mut self) -> Result<String, String> {
+    fn parse_type(&mut self) -> Result<String, Diagnostic> {
+        let span = self.span();
         let token = match self.peek() {
-            None => return Err("Unexpected end of file".to_string()),
+            None => return Err(self.unexpected_end()),
             Some(token) => token,
         };
 
-        let vartype = match token {
+        let mut vartype = match token {
             Token::Nanpa => "nanpa".to_string(),
+            Token::Lili => "lili".to_string(),
+            Token::Suli => "suli".to_string(),
+            Token::Sitelen => "sitelen".to_string(),
+            Token::Telo => "telo".to_string(),
+            Token::Telotu => "telo tu".to_string(),
+            Token::Lon => "lon".to_string(),
             Token::Linja => "linja".to_string(),
-            _ => return Err("Not a type".to_string()),
+            _ => return Err(Diagnostic::error("E0307", "Not a type", &span)),
         };
+        let is_number = matches!(token, Token::Nanpa | Token::Lili | Token::Suli);
         self.consume();
 
-        let token = match self.peek() {
-            None => return Err("Unexpected end of file".to_string()),
-            Some(token) => token,
-        };
-
-        let is_const = matches!(token, Token::Awen);
-
-        if is_const {
-            self.consume();
-            todo!("implement constants");
+        // nanpa unsigned. numbers are signed unless said otherwise
+        match self.peek() {
+            Some(Token::Signed | Token::Unsigned) if !is_number => {
+                return Err(Diagnostic::error("E0307", format!("a {vartype} can not be signed or unsigned"), &self.span())
+                    .with_note("only number types take 'signed' and 'unsigned'"));
+            }
+            Some(Token::Signed) => self.consume(),
+            Some(Token::Unsigned) => {
+                vartype += " unsigned";
+                self.consume();
+            }
+            _ => {}
         }
 
         Ok(vartype)
     }
 
-    fn parse_o_sin(&mut self) -> Result<OSinStatement, String> {
+    fn parse_o_sin(&mut self) -> Result<OSinStatement, Diagnostic> {
+        let span = self.span();
         // consume "o sin e"
         self.consume();
 
         // get type
-        let variable_type = self.parse_type()?;
+        let var_type = self.parse_type()?;
+
+        // lukin after the type lets other files see it
+        let public = self.expect(Token::Lukin);
+        if public {
+            self.consume();
+        }
+
+        // awen after the type makes it a constant
+        let constant = self.expect(Token::Awen);
+        if constant {
+            self.consume();
+        }
 
         // get name
         let name = self.parse_nimi_expression()?;
 
+        // li kama sama 'expr'
+        let mut expr = None;
+        if self.expect(Token::LiKamaSama) {
+            self.consume();
+            expr = Some(Box::new(self.parse_expression(Precedence::Undefined)?));
+        } else if constant {
+            return Err(Diagnostic::error("E0310", format!("{} is awen, so it needs a value", name.value), &self.span())
+                .with_note(format!("give it one with o sin e {var_type} awen {} li kama sama ...", name.value)));
+        }
+
         Ok(OSinStatement {
-            expr: None,
-            name: name,
-            var_type: "nanpa".to_string(),
+            expr,
+            name,
+            var_type,
+            constant,
+            public,
+            span,
         })
     }
 
-    fn parse_li_kama_sama(&mut self) -> Result<LiKamaSamaStatement, String> {
+    fn parse_li_kama_sama(&mut self) -> Result<LiKamaSamaStatement, Diagnostic> {
+        let span = self.span();
         let nimi = self.parse_nimi_expression()?;
 
         if !self.expect(Token::LiKamaSama) {
-            return Err("No 'li kama sama' in kama sama statement".to_string());
+            return Err(Diagnostic::error(
+                "E0308",
+                "No 'li kama sama' in kama sama statement",
+                &self.span(),
+            ));
         }
 
         self.consume();
@@ -1265,30 +2471,51 @@ impl Parser {
         Ok(LiKamaSamaStatement {
             nimi,
             expression: Box::new(expression),
+            span,
         })
     }
 
-    fn parse_parenthesis(&mut self) -> Result<Parenthesis, String> {
+    fn parse_parenthesis(&mut self) -> Result<Parenthesis, Diagnostic> {
+        let span = self.span();
         if !self.expect(Token::OpenParenthesis) {
-            return Err("not an opening parenthesis".to_string());
+            return Err(Diagnostic::error("E0309", "not an opening parenthesis", &span));
         };
         self.consume();
 
+        self.paren_blocks += 1;
         let mut nodes: Vec<Node> = Vec::new();
         loop {
-            if self.expect(Token::CloseParenthesis) {
-                self.consume();
-                break;
+            match self.peek() {
+                Some(Token::CloseParenthesis) => {
+                    self.consume();
+                    break;
+                }
+                None | Some(Token::Pali) => {
+                    self.paren_blocks -= 1;
+                    return Err(Diagnostic::error("E0315", "expected ')'", &self.span())
+                        .with_note(format!("to close the '(' at {span}")));
+                }
+                Some(_) => {}
+            }
+
+            let start = self.current_token;
+            match self.parse_statement() {
+                Ok(node) => nodes.push(node),
+                Err(err) => {
+                    self.diagnostics.push(err);
+                    self.synchronize(start);
+                }
             }
-            nodes.push(self.parse_statement());
         }
+        self.paren_blocks -= 1;
 
-        Ok(Parenthesis { nodes })
+        Ok(Parenthesis { nodes, span })
     }
 
-    fn parse_statement(&mut self) -> Node {
+    fn parse_statement(&mut self) -> Result<Node, Diagnostic> {
+        let span = self.span();
         let token = match self.peek() {
-            None => panic!("Out of tokens!"),
+            None => return Err(self.unexpected_end()),
             Some(token) => token,
         };
 
@@ -1297,17 +2524,15 @@ impl Parser {
         }
 
         let node = match token {
-            Token::OTawa => Node::Otawa(Box::new(self.parse_otawa().unwrap())),
-            Token::Asen => todo!("Asenpeli"),
-            Token::Tenpo => todo!("Tenpo"),
-            Token::Name(_) => Node::LiKamaSama(Box::new(self.parse_li_kama_sama().unwrap())),
-            Token::OSin => Node::OSin(Box::new(self.parse_o_sin().unwrap())),
+            Token::OTawa => Node::Otawa(Box::new(self.parse_otawa()?)),
+            Token::Asen => Node::Asenpeli(Box::new(self.parse_asen()?)),
+            Token::Name(_) => Node::LiKamaSama(Box::new(self.parse_li_kama_sama()?)),
+            Token::OSin => Node::OSin(Box::new(self.parse_o_sin()?)),
 	    Token::Pali => {
-		let pali = self.parse_pali().unwrap();
-		if pali.1.is_some() {
-		    Node::PaliDeclaration(Box::new(pali.1.unwrap()))
-		} else {
-		    Node::Pali(Box::new(pali.0.unwrap()))
+		match self.parse_pali()? {
+		    (_, Some(declaration)) => Node::PaliDeclaration(Box::new(declaration)),
+		    (Some(pali), None) => Node::Pali(Box::new(pali)),
+		    (None, None) => unreachable!(),
 		}
 	    },
             Token::OpeningTab(tabs) => {
@@ -1315,27 +2540,36 @@ impl Parser {
                 self.consume();
                 Node::OpeningTab(tabs)
             }
-            Token::OWeka => Node::OWeka(Box::new(self.parse_o_weka().unwrap())),
-            Token::O => Node::O(Box::new(self.parse_o().unwrap())),
+            Token::OWeka => Node::OWeka(Box::new(self.parse_o_weka()?)),
+            Token::O => Node::O(Box::new(self.parse_o()?)),
             Token::OpenParenthesis => {
-                Node::Parenthesis(Box::new(self.parse_parenthesis().unwrap()))
+                Node::Parenthesis(Box::new(self.parse_parenthesis()?))
+            }
+            Token::TenpoPi => Node::Tenpo(Box::new(self.parse_tenpo()?)),
+            Token::TenpoAlePi => Node::TenpoAle(Box::new(self.parse_tenpo_ale()?)),
+	    Token::Kepeken => Node::Kepeken(Box::new(self.parse_kepeken()?)),
+            _ => {
+                return Err(Diagnostic::error(
+                    "E0309",
+                    format!("{:?} can not start a statement", token),
+                    &span,
+                ))
             }
-            Token::TenpoPi => Node::Tenpo(Box::new(self.parse_tenpo().unwrap())),
-	    Token::Kepeken => Node::Kepeken(Box::new(self.parse_kepeken().unwrap())),
-            _ => todo!("{:#?}", token),
         };
-        node
+        Ok(node)
     }
 
+    // keeps going after errors, collecting them in `diagnostics`
     fn parse(&mut self) {
-        loop {
-            match self.peek() {
-                None => break,
-                Some(_) => {}
-            };
-
-            let node = self.parse_statement();
-            self.nodes.push(node);
+        while self.peek().is_some() {
+            let start = self.current_token;
+            match self.parse_statement() {
+                Ok(node) => self.nodes.push(node),
+                Err(err) => {
+                    self.diagnostics.push(err);
+                    self.synchronize(start);
+                }
+            }
         }
     }
 }
@@ -1344,6 +2578,12 @@ impl Parser {
 struct Variable {
     type_name: String,
     stack_pos: usize,
+    // declared with awen
+    constant: bool,
+    // the value of an awen that is known at compile time, it has no stack slot then
+    value: Option<Constant>,
+    // declared outside of a pali, it lives in .data or .bss under its own name
+    global: bool,
 }
 
 #[derive(Debug)]
@@ -1361,24 +2601,118 @@ enum EnvironmentName {
 struct Environment {
     names: HashMap<String, EnvironmentName>,
     stack_pointer: usize,
+    #[allow(unused)]
     tab_depth: usize,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TypeKind {
+    Integer,
+    // telo is an f32, telo tu an f64
+    Float,
+    // a truth value, 0 or 1 in a byte
+    Lon,
+    // pointer and length
+    Linja,
+}
+
 #[derive(Debug)]
 struct Type {
     size: usize,
+    signed: bool,
+    kind: TypeKind,
     name: String
 }
 
+impl Type {
+    // smallest and biggest value of a number type
+    fn get_range(&self) -> (i128, i128) {
+        let bits = self.size as u32 * 8;
+        if self.signed {
+            (-(1 << (bits - 1)), (1 << (bits - 1)) - 1)
+        } else {
+            (0, (1 << bits) - 1)
+        }
+    }
+
+    // whether every value of this number type is also one of `other`, like lili in nanpa
+    fn widens_to(&self, other: &Type) -> bool {
+        if self.kind != TypeKind::Integer || other.kind != TypeKind::Integer {
+            return false;
+        }
+        let (min, max) = self.get_range();
+        let (other_min, other_max) = other.get_range();
+        other_min <= min && max <= other_max
+    }
+}
+
+#[derive(Debug)]
+struct ConstantSymbol {
+    name: String,
+    size: usize,
+    bits: i128,
+    // lukin, so it is public
+    public: bool,
+}
+
+#[derive(Debug)]
+struct Global {
+    name: String,
+    size: usize,
+    // the data directive and values, none for a global that starts zeroed
+    data: Option<String>,
+    // other object files can link to it
+    public: bool,
+}
+
 #[derive(Debug)]
 struct Scope {
     functions: HashMap<String, Function>,
     types: HashMap<String, Rc<Type>>,
     envs: Vec<Environment>,
     label_counter: usize,
+    // directories searched for 'kepeken' modules, in order
+    search_path: Vec<PathBuf>,
+    // modules imported so far, linked in as lib/'module'.o
+    modules: Vec<String>,
+    // string literals, written to .rodata as __tp_linja_'index'
+    strings: Vec<String>,
+    // awen outside of a pali, written to .rodata under their own name
+    constants: Vec<ConstantSymbol>,
+    // variables outside of a pali, see `generate_globals`
+    globals: Vec<Global>,
+    // what the pali being generated hands back with 'o weka e'
+    return_type: Option<String>,
 }
 
 impl Scope {
+    fn find_module(&self, module: &str, span: &Span) -> Result<PathBuf, Diagnostic> {
+        let file = format!("{module}.tp");
+        for dir in &self.search_path {
+            let path = dir.join(&file);
+            if path.is_file() {
+                return Ok(path);
+            }
+        }
+
+        let mut diagnostic = Diagnostic::error("E0408", format!("No module named {module}"), span);
+        for dir in &self.search_path {
+            diagnostic = diagnostic.with_note(format!("looked for {}", dir.join(&file).display()));
+        }
+        Err(diagnostic.with_note("more directories can be added with TP_PATH"))
+    }
+
+    // the index of the string in .rodata, as __tp_linja_'index'
+    fn add_string(&mut self, value: &str) -> usize {
+        match self.strings.iter().position(|string| string == value) {
+            Some(index) => index,
+            None => {
+                self.strings.push(value.to_string());
+                self.strings.len() - 1
+            }
+        }
+    }
+
     fn get_environment_mut(&mut self) -> &mut Environment {
         self.envs.last_mut().unwrap()
     }
@@ -1387,10 +2721,13 @@ impl Scope {
         self.envs.last().unwrap()
     }
 
-    fn get_type(&self, name: &str) -> Option<Rc<Type>> {
-	self.types.get(name).cloned()
+    fn get_type(&self, name: &str, span: &Span) -> Result<Rc<Type>, Diagnostic> {
+	match self.types.get(name) {
+	    Some(vartype) => Ok(vartype.clone()),
+	    None => Err(Diagnostic::error("E0405", format!("No type named {name}"), span)),
+	}
     }
-    
+
     fn add_function(&mut self, pali: &PaliStatement) {
         self.functions.insert(
             pali.nimi.value.to_owned(),
@@ -1411,28 +2748,47 @@ impl Scope {
         );
     }
 
-    fn add_variable(&mut self, name: &str, variable_type: &str, reg: Option<&str>, writer: &mut BufWriter<fs::File>) -> &EnvironmentName {
-	let size = self.get_type(variable_type).unwrap().size;
+    fn add_variable(&mut self, name: &str, variable_type: &str, span: &Span, regs: Option<&[String]>, writer: &mut BufWriter<fs::File>) -> Result<&EnvironmentName, Diagnostic> {
+	let size = self.get_type(variable_type, span)?.size;
 
-	if reg.is_some() { 
-	    Generator::push_reg(reg.unwrap(), size, self, writer);
-	} else {
-	    println!("    sub rsp, {size}");
+	match regs {
+	    Some(regs) => Generator::push_regs(regs, self, writer)?,
+	    // a whole qword like everything else on the stack, so rsp stays aligned
+	    None => {
+		let slot = size.div_ceil(8) * 8;
+		writeln!(writer, "    sub rsp, {slot}")?;
+		self.get_environment_mut().stack_pointer += slot;
+	    }
 	}
-	
-        self.get_environment_mut().add_name(name, size, variable_type)
+
+        Ok(self.get_environment_mut().add_name(name, size, variable_type, false))
+    }
+
+    // a new name may shadow one from an outer scope, but not one from the same scope
+    fn expect_new_name(&self, nimi: &NimiExpression) -> Result<(), Diagnostic> {
+        match self.get_environment().get_variable(&nimi.value) {
+            Err(_) => Ok(()),
+            Ok(_) => Err(Diagnostic::error(
+                "E0403",
+                format!("There's already a variable named {} in this scope", nimi.value),
+                &nimi.span,
+            )),
+        }
     }
 
-    fn get_variable(&self, name: &str) -> Result<(&Variable, isize), String> {
+    fn get_variable(&self, name: &str, span: &Span) -> Result<(&Variable, isize), Diagnostic> {
         let mut found_env_index = 0;
         let mut variable: Option<&Variable> = None;
-        for env in (&self.envs).into_iter().enumerate().rev() {
+        for env in self.envs.iter().enumerate().rev() {
             let result = env.1.get_variable(name);
             match result {
-                Err(err) => {
+                Err(_) => {
                     if env.0 == 0 {
-                        println!("{:#?}", self);
-                        return Err(err);
+                        return Err(Diagnostic::error(
+                            "E0401",
+                            format!("No variable named {name}"),
+                            span,
+                        ));
                     }
                 }
                 Ok(ok) => {
@@ -1443,79 +2799,101 @@ impl Scope {
             }
         }
 
-        if variable.is_none() {
-            unreachable!();
-        }
-
-        let mut start_offset = 0usize;
-        for (index, env) in (&self.envs).iter().enumerate() {
-            if index != found_env_index {
-                start_offset += env.stack_pointer;
-            } else {
-                start_offset += variable.unwrap().stack_pos;
-                break;
-            }
-        }
-
-	let mut base_offset  = 0usize;
-        for (index, env) in (&self.envs).iter().enumerate() {
-	    if self.envs.len() - 1 == index {
-		break;
-	    }
-            base_offset += env.stack_pointer;
-        }
+        let variable = match variable {
+            Some(variable) => variable,
+            None => unreachable!(),
+        };
 
-	Ok((variable.unwrap(), start_offset as isize - base_offset as isize))
-        
+        // rbp points right above the environment of the function (the one
+        // after the file scope), so everything pushed in the environments
+        // between it and the variable's own one lies in between
+        let offset: usize = self
+            .envs
+            .iter()
+            .take(found_env_index)
+            .skip(1)
+            .map(|env| env.stack_pointer)
+            .sum::<usize>()
+            + variable.stack_pos;
+
+	Ok((variable, offset as isize))
     }
 
-    fn get_function(&self, name: &str) -> Result<&Function, String> {
+    fn get_function(&self, name: &str, span: &Span) -> Result<&Function, Diagnostic> {
         match self.functions.get(name) {
             Some(func) => Ok(func),
-            None => Err(format!("No function named {name}")),
+            None => Err(Diagnostic::error(
+                "E0402",
+                format!("No function named {name}"),
+                span,
+            )),
         }
     }
 }
 
 impl Environment {
-    fn add_name(&mut self, name: &str, size: usize, variable_type: &str) -> &EnvironmentName {
+    fn add_name(&mut self, name: &str, _size: usize, variable_type: &str, constant: bool) -> &EnvironmentName {
         self.names.insert(
             name.to_string(),
             EnvironmentName::Variable(Variable {
                 type_name: variable_type.to_string(),
                 stack_pos: self.stack_pointer,
+                constant,
+                value: None,
+                global: false,
+            }),
+        );
+
+        self.names.get(name).unwrap()
+    }
+
+    fn add_constant(&mut self, name: &str, variable_type: &str, value: Constant) -> &EnvironmentName {
+        self.names.insert(
+            name.to_string(),
+            EnvironmentName::Variable(Variable {
+                type_name: variable_type.to_string(),
+                stack_pos: 0,
+                constant: true,
+                value: Some(value),
+                global: false,
             }),
-        );	
-        
-        self.names.get(&name.to_string()).unwrap()
+        );
+
+        self.names.get(name).unwrap()
+    }
+
+    fn add_global(&mut self, name: &str, variable_type: &str) -> &EnvironmentName {
+        self.names.insert(
+            name.to_string(),
+            EnvironmentName::Variable(Variable {
+                type_name: variable_type.to_string(),
+                stack_pos: 0,
+                constant: false,
+                value: None,
+                global: true,
+            }),
+        );
+
+        self.names.get(name).unwrap()
     }
 
     fn get_name(&self, name: &str) -> Option<&EnvironmentName> {
-        self.names.get(&name.to_string())
+        self.names.get(name)
     }
 
     fn get_variable(&self, name: &str) -> Result<&Variable, String> {
-        if self.get_name(name).is_none() {
-            return Err(format!("{name} is not a valid name"));
-        } else {
-            match self.get_name(name).unwrap() {
-                EnvironmentName::Variable(var) => return Ok(&var),
-            }
+        match self.get_name(name) {
+            None => Err(format!("{name} is not a valid name")),
+            Some(EnvironmentName::Variable(var)) => Ok(var),
         }
     }
 }
 
 struct Generator {
     nodes: Vec<Node>,
-    current_node: usize,
 }
 
 impl Generator {
-    fn next(&mut self) -> Option<&Node> {
-        self.current_node += 1;
-        return self.nodes.get(self.current_node);
-    }
-
     fn get_argument_register(arg: usize) -> String {
 	match arg {
 	    0 => "rdi",
@@ -1528,376 +2906,1190 @@ impl Generator {
 	}.to_string()
     }
 
+    // System V hands telo arguments out from their own registers
+    fn get_float_argument_register(arg: usize) -> String {
+	format!("xmm{}", arg.min(7))
+    }
+
+    // the registers every argument of a call is passed in, one per qword and
+    // counted separately for telo values and everything else
+    fn get_argument_registers(types: &[Rc<Type>]) -> Vec<Vec<String>> {
+        let mut next_register = 0;
+        let mut next_float_register = 0;
+        types
+            .iter()
+            .map(|argument_type| {
+                if argument_type.kind == TypeKind::Float {
+                    next_float_register += 1;
+                    return vec![Self::get_float_argument_register(next_float_register - 1)];
+                }
+                let count = argument_type.size.div_ceil(8);
+                next_register += count;
+                (next_register - count..next_register).map(Self::get_argument_register).collect()
+            })
+            .collect()
+    }
+
+    // the memory operand of the `part`th qword of a variable. globals are addressed relative to rip,
+    // which fasm does on its own for a label
+    fn get_address(name: &str, variable: &Variable, offset: isize, part: usize) -> String {
+        match (variable.global, part) {
+            (true, 0) => format!("[{name}]"),
+            (true, _) => format!("[{name} + {}]", part * 8),
+            (false, _) => format!("[rbp - {}]", offset - part as isize * 8),
+        }
+    }
+
+    // the data directive for a value of `size` bytes, like dd for a dword
+    fn get_data_directive(size: usize) -> &'static str {
+        match size {
+            1 => "db",
+            2 => "dw",
+            4 => "dd",
+            _ => "dq",
+        }
+    }
+
     fn get_word_from_size(size: usize) -> String {
 	String::from(match size {
 	    1 => "byte",
 	    2 => "word",
 	    4 => "dword",
 	    8 => "qword",
-	    _ => todo!()
+	    _ => unreachable!("no operand size of {size} bytes"),
 	})
     }
-    
-    fn push(i: isize, size: usize, scope: &mut Scope, writer: &mut BufWriter<fs::File>) {
-        writeln!(writer, "    mov r8, {i}");
-        writeln!(writer, "    push r8");
-        scope.get_environment_mut().stack_pointer += size;
-    }
 
-    fn push_reg(reg: &str, size: usize, scope: &mut Scope, writer: &mut BufWriter<fs::File>) {
-        writeln!(writer, "    push {} {reg}", Self::get_word_from_size(size));
-        scope.get_environment_mut().stack_pointer += size;
+    // the part of a register that holds the lowest `size` bytes, al for rax and r8b for r8
+    fn get_register_part(reg: &str, size: usize) -> String {
+        let parts = match reg {
+            "rax" => ["al", "ax", "eax"],
+            "rcx" => ["cl", "cx", "ecx"],
+            "rdx" => ["dl", "dx", "edx"],
+            "rdi" => ["dil", "di", "edi"],
+            "rsi" => ["sil", "si", "esi"],
+            _ => {
+                // r8 to r15
+                return match size {
+                    1 => format!("{reg}b"),
+                    2 => format!("{reg}w"),
+                    4 => format!("{reg}d"),
+                    _ => reg.to_string(),
+                };
+            }
+        };
+        match size {
+            1 => parts[0],
+            2 => parts[1],
+            4 => parts[2],
+            _ => reg,
+        }
+        .to_string()
     }
 
-    fn pop_reg(reg: &str, size: usize, scope: &mut Scope, writer: &mut BufWriter<fs::File>) {
-        writeln!(writer, "    pop {} {reg}", Self::get_word_from_size(size));
-        scope.get_environment_mut().stack_pointer -= size;
+    // values on the stack always take a whole qword, numbers smaller than that
+    // are sign or zero extended first, see `load`
+    fn push(i: i128, scope: &mut Scope, writer: &mut BufWriter<fs::File>) -> Result<(), Diagnostic> {
+        writeln!(writer, "    mov r8, {i}")?;
+        writeln!(writer, "    push r8")?;
+        scope.get_environment_mut().stack_pointer += 8;
+        Ok(())
     }
 
-    fn mov(to: &str, size: usize, from: &str, writer: &mut BufWriter<fs::File>) {
-        writeln!(writer, "    mov {to}, {} {from}", Self::get_word_from_size(size));
+    // xmm registers can not be pushed, so they go through [rsp] without touching another register
+    fn push_reg(reg: &str, scope: &mut Scope, writer: &mut BufWriter<fs::File>) -> Result<(), Diagnostic> {
+        if reg.starts_with("xmm") {
+            writeln!(writer, "    sub rsp, 8")?;
+            writeln!(writer, "    movq qword [rsp], {reg}")?;
+        } else {
+            writeln!(writer, "    push qword {reg}")?;
+        }
+        scope.get_environment_mut().stack_pointer += 8;
+        Ok(())
     }
 
-    fn zero(reg: &str, writer: &mut BufWriter<fs::File>) {
-        writeln!(writer, "    xor {reg}, {reg}");
+    fn pop_reg(reg: &str, scope: &mut Scope, writer: &mut BufWriter<fs::File>) -> Result<(), Diagnostic> {
+        if reg.starts_with("xmm") {
+            writeln!(writer, "    movq {reg}, qword [rsp]")?;
+            writeln!(writer, "    add rsp, 8")?;
+        } else {
+            writeln!(writer, "    pop qword {reg}")?;
+        }
+        scope.get_environment_mut().stack_pointer -= 8;
+        Ok(())
     }
 
-    fn generate_nanpa_expression(nanpa_expression: &NanpaExpression, scope: &mut Scope, writer: &mut BufWriter<fs::File>) {
-        Generator::push(nanpa_expression.value, 8, scope, writer);
+    // values bigger than a qword take one register per qword and are pushed in that order,
+    // so a linja is its pointer followed by its length
+    fn push_regs(regs: &[String], scope: &mut Scope, writer: &mut BufWriter<fs::File>) -> Result<(), Diagnostic> {
+        for reg in regs {
+            Self::push_reg(reg, scope, writer)?;
+        }
+        Ok(())
     }
 
-    fn generate_nimi_expression(nimi_expression: &NimiExpression, scope: &mut Scope, writer: &mut BufWriter<fs::File>) {
-        let (name, offset) = scope.get_variable(&nimi_expression.value).unwrap();
-        writeln!(writer, );
-        writeln!(writer, 
-            "    ; Getting value of variable {} with offset {}",
-            nimi_expression.value, offset
-        );
-        Self::push_reg(format!("[rbp - {}]", offset).as_str(), scope.get_type(&name.type_name).unwrap().size, scope, writer);
+    fn pop_regs(regs: &[String], scope: &mut Scope, writer: &mut BufWriter<fs::File>) -> Result<(), Diagnostic> {
+        for reg in regs.iter().rev() {
+            Self::pop_reg(reg, scope, writer)?;
+        }
+        Ok(())
     }
 
-    fn generate_kepeken(kepeken_statement: &KepekenStatement) {
-	todo!();
-    }
-    
-    fn generate_nimi_new(
-        nimi_expression: &NimiExpression,
-        variable_type: &str,
-        scope: &mut Scope,
-	writer: &mut BufWriter<fs::File>
-    ) {
-        match scope.get_environment().get_variable(&nimi_expression.value) {
-            Err(_) => {
-                writeln!(writer, );
-                writeln!(writer, 
-                    "    ; new {} {}",
-                    variable_type, nimi_expression.value,
-                );
-                
-                scope.add_variable(&nimi_expression.value, variable_type, None, writer);
-            }
-            Ok(_) => {
-                panic!(
-                    "There's already a variable named {} in this scope",
-                    nimi_expression.value
-                );
-            }
+    // reads a number of the given type from `from` into all of `reg`, sign or zero extended
+    fn load(reg: &str, value_type: &Type, from: &str, writer: &mut BufWriter<fs::File>) -> Result<(), Diagnostic> {
+        let word = Self::get_word_from_size(value_type.size);
+        // a telo is just its bits
+        let signed = value_type.signed && value_type.kind == TypeKind::Integer;
+        match (value_type.size, signed) {
+            (8, _) => writeln!(writer, "    mov {reg}, {word} {from}")?,
+            (4, true) => writeln!(writer, "    movsxd {reg}, {word} {from}")?,
+            // writing the low dword clears the rest of the register
+            (4, false) => writeln!(writer, "    mov {}, {word} {from}", Self::get_register_part(reg, 4))?,
+            (_, true) => writeln!(writer, "    movsx {reg}, {word} {from}")?,
+            (_, false) => writeln!(writer, "    movzx {reg}, {word} {from}")?,
         }
+        Ok(())
     }
 
-    fn generate_nimi_recieve_stack(
-        nimi_expression: &NimiExpression,
-        variable_type: Option<&str>,
-        scope: &mut Scope,
-	writer: &mut BufWriter<fs::File>
-    ) {
-        match scope.get_variable(&nimi_expression.value) {
-            Err(_) => {
-                panic!("No variable named {}", nimi_expression.value);
-            }
-            Ok((name, offset)) => {
-                writeln!(writer, );
-                writeln!(writer, "    ; Setting variable {}", nimi_expression.value);
-		let size = scope.get_type(&name.type_name).unwrap().size;
-                Generator::pop_reg("r9", scope.get_type(&name.type_name).unwrap().size, scope, writer);
-		
-                Generator::mov(format!("[rbp - {offset}]").as_str(), size, "r9", writer);
-            }
-        };
+    // wraps a result that may have grown past its type back into it
+    fn extend(reg: &str, value_type: &Type, writer: &mut BufWriter<fs::File>) -> Result<(), Diagnostic> {
+        if value_type.size >= 8 || value_type.kind == TypeKind::Float {
+            return Ok(());
+        }
+        Self::load(reg, value_type, &Self::get_register_part(reg, value_type.size), writer)
     }
 
-    fn generate_o_sin(osin: &OSinStatement, scope: &mut Scope, writer: &mut BufWriter<fs::File>) {
-        match &osin.expr {
-            Some(expr) => Self::generate_expression(&expr, scope, writer),
-            None => {}
-        };
-
-        Self::generate_nimi_new(&osin.name, &osin.var_type, scope, writer);
+    fn get_return_registers(return_type: &Type) -> Vec<String> {
+        if return_type.kind == TypeKind::Float {
+            return vec!["xmm0".to_string()];
+        }
+        ["rax", "rdx"]
+            .iter()
+            .take(return_type.size.div_ceil(8))
+            .map(|reg| reg.to_string())
+            .collect()
     }
 
-    fn generate_li_kama_sama_statement(kama_sama: &LiKamaSamaStatement, scope: &mut Scope, writer: &mut BufWriter<fs::File>) {
-        writeln!(writer, "");
-
-        Self::generate_expression(&kama_sama.expression, scope, writer);
-
-        Self::generate_nimi_recieve_stack(&kama_sama.nimi, None, scope, writer);
+    // type of a value that has to fit in a single register
+    fn get_scalar_type(expr: &Expression, scope: &Scope) -> Result<Rc<Type>, Diagnostic> {
+        let type_name = expr.get_type_name(scope)?;
+        let scalar_type = scope.get_type(&type_name, expr.span())?;
+        if scalar_type.size > 8 {
+            return Err(Diagnostic::error("E0404", format!("a {type_name} can not be used here"), expr.span())
+                .with_note("only values that fit in a register can be calculated with or used as conditions"));
+        }
+        Ok(scalar_type)
     }
 
-    fn generate_unary_expression(unary: &UnaryExpression, scope: &mut Scope, writer: &mut BufWriter<fs::File>) {
-        match unary {
-            UnaryExpression::Nanpa(nanpa) => Self::generate_nanpa_expression(nanpa, scope, writer),
-            UnaryExpression::Nimi(nimi) => Self::generate_nimi_expression(nimi, scope, writer),
-            UnaryExpression::O(o) => Self::generate_o(o, scope, writer),
-            UnaryExpression::Linja(linja) => {}
+    // conditions and the operands of ala, en and anu
+    fn expect_lon(expr: &Expression, what: &str, scope: &Scope) -> Result<(), Diagnostic> {
+        if expr.fits_type("lon", scope)? {
+            return Ok(());
         }
+        Err(Diagnostic::error(
+            "E0410",
+            format!("{what} has to be a lon, not a {}", expr.get_type_name(scope)?),
+            expr.span(),
+        )
+        .with_note("compare it to get a lon, like X != 0"))
     }
 
-    fn generate_binary_expression(binary: &BinaryExpression, scope: &mut Scope, writer: &mut BufWriter<fs::File>) {
-        Self::generate_expression(&binary.lhs, scope, writer);
-        Self::generate_expression(&binary.rhs, scope, writer);
-	let sizel = scope.get_type(&binary.lhs.get_type_name(scope)).unwrap().size;
-	let sizer = scope.get_type(&binary.rhs.get_type_name(scope)).unwrap().size;
-        Self::pop_reg("r9", sizel, scope, writer);
-        Self::pop_reg("r8", sizer, scope, writer);
-	let size: usize = scope.get_type(binary.get_type_name(scope).as_str()).unwrap().size;
-	
-        match binary.kind {
-            BinaryExpressionType::Add => {
-                writeln!(writer, "    add r8, r9");
-                Self::push_reg("r8", size, scope, writer);
-            }
-            BinaryExpressionType::Subtract => {
-                writeln!(writer, "    sub r8, r9");
-                Self::push_reg("r8", size, scope, writer);
-            }
-            BinaryExpressionType::Multiply => {
-                Self::mov("rax", size, "r8", writer);
-                writeln!(writer, "    mul r9");
-                Self::push_reg("rax", size, scope, writer);
-            }
-            BinaryExpressionType::Divide => {
-                Self::zero("rdx", writer);
-                Self::mov("rax", size, "r8", writer);
-                writeln!(writer, "    div r9");
-                Self::push_reg("rax", size, scope, writer);
-            }
-            BinaryExpressionType::Equals => {
-                Self::zero("ecx", writer);
-                writeln!(writer, "    cmp r8, r9");
-                writeln!(writer, "    setz cl");
-                Self::push_reg("rcx", size, scope, writer);
-            }
-            _ => {}
+    // suffix of the set/j instruction that checks a comparison after `cmp lhs, rhs`
+    fn get_condition_code(kind: BinaryExpressionType, signed: bool) -> &'static str {
+        match (kind, signed) {
+            (BinaryExpressionType::Equals, _) => "e",
+            (BinaryExpressionType::Unequals, _) => "ne",
+            (BinaryExpressionType::LessThan, true) => "l",
+            (BinaryExpressionType::LessThan, false) => "b",
+            (BinaryExpressionType::GreaterThan, true) => "g",
+            (BinaryExpressionType::GreaterThan, false) => "a",
+            (BinaryExpressionType::LessEquals, true) => "le",
+            (BinaryExpressionType::LessEquals, false) => "be",
+            (BinaryExpressionType::GreaterEquals, true) => "ge",
+            (BinaryExpressionType::GreaterEquals, false) => "ae",
+            _ => unreachable!("{:?} is not a comparison", kind),
         }
     }
 
-    fn generate_expression(expression: &Expression, scope: &mut Scope, writer: &mut BufWriter<fs::File>) {
-        match expression {
-            Expression::Unary(unary) => Self::generate_unary_expression(unary, scope, writer),
-            Expression::Binary(binary) => Self::generate_binary_expression(binary, scope, writer),
-        };
+    fn mov(to: &str, size: usize, from: &str, writer: &mut BufWriter<fs::File>) -> Result<(), Diagnostic> {
+        writeln!(writer, "    mov {to}, {} {from}", Self::get_word_from_size(size))?;
+        Ok(())
     }
 
-    fn generate_otawa(otawa: &OtawaStatement, scope: &mut Scope, writer: &mut BufWriter<fs::File>) {
-        Self::generate_expression(&otawa.expr, scope, writer);
-	let size = scope.get_type(&otawa.expr.get_type_name(scope)).unwrap().size;
-        writeln!(writer, );
-        writeln!(writer, "    ; Exit call:");
-        Self::pop_reg("rdi", size, scope, writer);
-        Self::mov("rax", size, "60", writer);
-        writeln!(writer, "    syscall");
-        writeln!(writer, );
+    fn zero(reg: &str, writer: &mut BufWriter<fs::File>) -> Result<(), Diagnostic> {
+        writeln!(writer, "    xor {reg}, {reg}")?;
+        Ok(())
+    }
+
+    // the bits of a constant as a value of the type, for a constant already checked with `fits_type`
+    fn get_constant_bits(constant: Constant, type_name: &str, span: &Span, scope: &Scope) -> Result<i128, Diagnostic> {
+        let vartype = scope.get_type(type_name, span)?;
+        let value = match (Self::get_constant_as(constant, &vartype), vartype.kind) {
+            // ala as a number is zero, and zero has the same bits in every type
+            (Constant::Lon(value), _) => return Ok(value as i128),
+            (Constant::Telo(value), _) => value,
+            (Constant::Nanpa(value), _) => {
+                let (min, max) = vartype.get_range();
+                if !(min..=max).contains(&value) {
+                    return Err(Diagnostic::error("E0409", format!("{value} does not fit in a {}", vartype.name), span)
+                        .with_note(format!("a {} goes from {min} to {max}", vartype.name)));
+                }
+                return Ok(value);
+            }
+        };
+
+        // the bits of the number as a telo or telo tu
+        if vartype.size == 4 {
+            let single = value as f32;
+            if single.is_infinite() && value.is_finite() {
+                return Err(Diagnostic::error("E0409", format!("{value:e} does not fit in a {}", vartype.name), span)
+                    .with_note(format!("a {} goes up to {:e}, use a telo tu for bigger numbers", vartype.name, f32::MAX)));
+            }
+            return Ok(single.to_bits() as i128);
+        }
+        Ok(value.to_bits() as i128)
+    }
+
+    // the constant as a value of the type, so 1 in a telo is 1.0 and 0.1 in a telo is rounded like an f32
+    fn get_constant_as(constant: Constant, vartype: &Type) -> Constant {
+        match (constant.as_number(), vartype.kind) {
+            (Some(Constant::Nanpa(value)), TypeKind::Float) if vartype.size == 4 => Constant::Telo(value as f32 as f64),
+            (Some(Constant::Telo(value)), TypeKind::Float) if vartype.size == 4 => Constant::Telo(value as f32 as f64),
+            (Some(Constant::Nanpa(value)), TypeKind::Float) => Constant::Telo(value as f64),
+            (Some(number), TypeKind::Integer | TypeKind::Float) => number,
+            _ => constant,
+        }
+    }
+
+    fn generate_constant(constant: Constant, type_name: &str, span: &Span, scope: &mut Scope, writer: &mut BufWriter<fs::File>) -> Result<(), Diagnostic> {
+        let bits = Self::get_constant_bits(constant, type_name, span, scope)?;
+        if let Constant::Telo(value) = Self::get_constant_as(constant, &*scope.get_type(type_name, span)?) {
+            writeln!(writer, "    ; {value} as {type_name}")?;
+        }
+        Generator::push(bits, scope, writer)
+    }
+
+    fn generate_nimi_expression(nimi_expression: &NimiExpression, scope: &mut Scope, writer: &mut BufWriter<fs::File>) -> Result<(), Diagnostic> {
+        let (name, offset) = scope.get_variable(&nimi_expression.value, &nimi_expression.span)?;
+        let variable_type = scope.get_type(&name.type_name, &nimi_expression.span)?;
+        if let Some(value) = name.value {
+            writeln!(writer)?;
+            writeln!(writer, "    ; awen {}", nimi_expression.value)?;
+            return Self::generate_constant(value, &variable_type.name, &nimi_expression.span, scope, writer);
+        }
+        let size = variable_type.size;
+        let addresses: Vec<String> = (0..size.div_ceil(8))
+            .map(|part| Self::get_address(&nimi_expression.value, name, offset, part))
+            .collect();
+        writeln!(writer)?;
+        writeln!(writer,
+            "    ; Getting value of variable {} at {}",
+            nimi_expression.value, addresses[0]
+        )?;
+        if size <= 8 {
+            Self::load("r8", &variable_type, &addresses[0], writer)?;
+            return Self::push_reg("r8", scope, writer);
+        }
+        // the first qword of the value sits at the highest address
+        for address in addresses.iter().rev() {
+            Self::push_reg(address, scope, writer)?;
+        }
+        Ok(())
+    }
+
+    fn generate_linja_expression(linja: &LinjaExpression, scope: &mut Scope, writer: &mut BufWriter<fs::File>) -> Result<(), Diagnostic> {
+        let index = scope.add_string(&linja.value);
+
+        writeln!(writer)?;
+        writeln!(writer, "    ; linja {index}")?;
+        writeln!(writer, "    lea r8, [__tp_linja_{index}]")?;
+        Self::push_reg("r8", scope, writer)?;
+        Self::push(linja.value.len() as i128, scope, writer)
+    }
+
+    // every line of the string is written as is, except that {X} becomes the stack slot of X.
+    // pushes and pops in there are not tracked, so they have to be balanced
+    fn generate_asen(asen: &AsenpeliStatement, scope: &mut Scope, writer: &mut BufWriter<fs::File>) -> Result<(), Diagnostic> {
+        if scope.envs.len() == 1 {
+            return Err(Diagnostic::error("E0415", "asen outside of a pali would never run", &asen.span)
+                .with_note("put it in the body of a pali"));
+        }
+        writeln!(writer, "  ; asen")?;
+        for line in asen.value.lines() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut resolved = String::new();
+            let mut rest = line;
+            while let Some(start) = rest.find('{') {
+                let end = match rest[start..].find('}') {
+                    Some(end) => start + end,
+                    None => {
+                        return Err(Diagnostic::error("E0407", format!("Unclosed operand in asen line '{line}'"), &asen.span)
+                            .with_note("operands are written as {X}"));
+                    }
+                };
+                let name = rest[start + 1..end].trim();
+                let (variable, offset) = scope.get_variable(name, &asen.span)?;
+                // an awen known at compile time is just its value
+                if let Some(value) = variable.value {
+                    resolved += &rest[..start];
+                    resolved += &Self::get_constant_bits(value, &variable.type_name, &asen.span, scope)?.to_string();
+                    rest = &rest[end + 1..];
+                    continue;
+                }
+                let size = scope.get_type(&variable.type_name, &asen.span)?.size;
+                if size > 8 {
+                    return Err(Diagnostic::error("E0407", format!("{name} does not fit in a single operand"), &asen.span)
+                        .with_note(format!("{name} is a {}", variable.type_name)));
+                }
+
+                resolved += &rest[..start];
+                resolved += &format!("{} {}", Self::get_word_from_size(size), Self::get_address(name, variable, offset, 0));
+                rest = &rest[end + 1..];
+            }
+            resolved += rest;
+
+            writeln!(writer, "    {resolved}")?;
+        }
+        Ok(())
+    }
+
+    // only the signatures of a module are needed here, its code is compiled on its own
+    fn generate_kepeken(kepeken: &KepekenStatement, scope: &mut Scope, writer: &mut BufWriter<fs::File>) -> Result<(), Diagnostic> {
+	let module = &kepeken.nimi.value;
+	if scope.modules.contains(module) {
+	    return Ok(());
+	}
+
+	let path = scope.find_module(module, &kepeken.nimi.span)?;
+	let (nodes, diagnostics) = parse_file(&path.to_string_lossy(), false);
+	let count = diagnostics.len();
+	if let Some(mut diagnostic) = diagnostics.into_iter().next() {
+	    if count > 1 {
+		diagnostic = diagnostic.with_note(format!("{} more errors in {}", count - 1, path.display()));
+	    }
+	    return Err(diagnostic.with_note(format!("in module {module}, imported at {}", kepeken.nimi.span)));
+	}
+
+	writeln!(writer, "  ; kepeken \"{module}\"")?;
+	for node in &nodes {
+	    match node {
+		Node::Pali(pali) => {
+		    scope.add_function(pali);
+		    writeln!(writer, "extrn {}", pali.nimi.value)?;
+		}
+		// only what the module marks lukin can be used here.
+		// awen of a module are folded in here as well, their symbols are only for asen and C
+		Node::OSin(osin) if osin.public && osin.constant => {
+		    let value = osin.expr.as_ref().and_then(|expr| expr.evaluate(Some(scope)));
+		    if let Some(value) = value {
+			let value = Self::get_constant_as(value, &*scope.get_type(&osin.var_type, &osin.span)?);
+			scope.envs[0].add_constant(&osin.name.value, &osin.var_type, value);
+		    }
+		}
+		Node::OSin(osin) if osin.public => {
+		    scope.envs[0].add_global(&osin.name.value, &osin.var_type);
+		    writeln!(writer, "extrn {}", osin.name.value)?;
+		}
+		_ => {}
+	    }
+	}
+	scope.modules.push(module.clone());
+	Ok(())
+    }
+
+    // with `initialized` the value is already on top of the stack and that slot becomes the variable
+    fn generate_nimi_new(
+        nimi_expression: &NimiExpression,
+        variable_type: &str,
+        initialized: bool,
+        constant: bool,
+        scope: &mut Scope,
+	writer: &mut BufWriter<fs::File>
+    ) -> Result<(), Diagnostic> {
+        scope.expect_new_name(nimi_expression)?;
+        writeln!(writer)?;
+        writeln!(writer,
+            "    ; new {} {}",
+            variable_type, nimi_expression.value,
+        )?;
+
+        if initialized {
+            let size = scope.get_type(variable_type, &nimi_expression.span)?.size;
+            scope.get_environment_mut().add_name(&nimi_expression.value, size, variable_type, constant);
+        } else {
+            scope.add_variable(&nimi_expression.value, variable_type, &nimi_expression.span, None, writer)?;
+        }
+        Ok(())
+    }
+
+    fn generate_nimi_recieve_stack(
+        nimi_expression: &NimiExpression,
+        scope: &mut Scope,
+	writer: &mut BufWriter<fs::File>
+    ) -> Result<(), Diagnostic> {
+        let (name, offset) = scope.get_variable(&nimi_expression.value, &nimi_expression.span)?;
+	let size = scope.get_type(&name.type_name, &nimi_expression.span)?.size;
+        let addresses: Vec<String> = (0..size.div_ceil(8))
+            .map(|part| Self::get_address(&nimi_expression.value, name, offset, part))
+            .collect();
+        writeln!(writer)?;
+        writeln!(writer, "    ; Setting variable {}", nimi_expression.value)?;
+        if size <= 8 {
+            Generator::pop_reg("r9", scope, writer)?;
+            return Generator::mov(&addresses[0], size, &Self::get_register_part("r9", size), writer);
+        }
+        for address in &addresses {
+            Generator::pop_reg("r9", scope, writer)?;
+            Generator::mov(address, 8, "r9", writer)?;
+        }
+        Ok(())
+    }
+
+    fn generate_o_sin(osin: &OSinStatement, scope: &mut Scope, writer: &mut BufWriter<fs::File>) -> Result<(), Diagnostic> {
+        if osin.public && scope.envs.len() != 1 {
+            return Err(Diagnostic::error("E0413", format!("{} is inside a pali, so other files can not see it", osin.name.value), &osin.name.span)
+                .with_note("only variables outside of a pali can be lukin"));
+        }
+        if let Some(expr) = &osin.expr {
+            if !expr.fits_type(&osin.var_type, scope)? {
+                return Err(Diagnostic::error(
+                    "E0404",
+                    format!("{} is a {} but its value is a {}", osin.name.value, osin.var_type, expr.get_type_name(scope)?),
+                    expr.span(),
+                ));
+            }
+            if osin.constant {
+                if let Some(value) = expr.evaluate(Some(scope)) {
+                    return Self::generate_awen(osin, value, scope, writer);
+                }
+                if scope.envs.len() == 1 {
+                    return Err(Diagnostic::error("E0412", format!("the value of {} is not known at compile time", osin.name.value), expr.span())
+                        .with_note("an awen outside of a pali can only use literals and other awen"));
+                }
+            }
+        }
+
+        // nothing runs outside of a pali, so the value has to be there before the program starts
+        if scope.envs.len() == 1 {
+            return Self::generate_global(osin, scope, writer);
+        }
+
+        if let Some(expr) = &osin.expr {
+            // the value is evaluated before the name exists, so it can still
+            // refer to a variable of the same name from an outer scope
+            Self::generate_expression_as(expr, &osin.var_type, scope, writer)?;
+        }
+
+        Self::generate_nimi_new(&osin.name, &osin.var_type, osin.expr.is_some(), osin.constant, scope, writer)
+    }
+
+    // an awen with a value known at compile time takes no stack slot, every use of it is replaced by the value.
+    // outside of a pali it is also written to .rodata, for asen and other object files
+    fn generate_awen(osin: &OSinStatement, value: Constant, scope: &mut Scope, writer: &mut BufWriter<fs::File>) -> Result<(), Diagnostic> {
+        let (nimi, variable_type) = (&osin.name, osin.var_type.as_str());
+        let span = osin.expr.as_ref().map_or(&nimi.span, |expr| expr.span());
+        scope.expect_new_name(nimi)?;
+
+        let bits = Self::get_constant_bits(value, variable_type, span, scope)?;
+        let value = Self::get_constant_as(value, &*scope.get_type(variable_type, &nimi.span)?);
+        writeln!(writer)?;
+        writeln!(writer, "    ; awen {variable_type} {} = {value}", nimi.value)?;
+        if scope.envs.len() == 1 {
+            let size = scope.get_type(variable_type, &nimi.span)?.size;
+            scope.constants.push(ConstantSymbol { name: nimi.value.clone(), size, bits, public: osin.public });
+        }
+        scope.get_environment_mut().add_constant(&nimi.value, variable_type, value);
+        Ok(())
+    }
+
+    // a variable outside of a pali, in .data with its value or zeroed in .bss
+    fn generate_global(osin: &OSinStatement, scope: &mut Scope, writer: &mut BufWriter<fs::File>) -> Result<(), Diagnostic> {
+        let (nimi, variable_type) = (&osin.name, osin.var_type.as_str());
+        scope.expect_new_name(nimi)?;
+
+        let size = scope.get_type(variable_type, &nimi.span)?.size;
+        let data = match &osin.expr {
+            None => None,
+            Some(expr) => {
+                let linja = match expr.as_ref() {
+                    Expression::Unary(unary) => match unary.as_ref() {
+                        UnaryExpression::Linja(linja) => Some(linja),
+                        _ => None,
+                    },
+                    Expression::Binary(_) => None,
+                };
+                Some(match (expr.evaluate(Some(scope)), linja) {
+                    (Some(value), _) => {
+                        let bits = Self::get_constant_bits(value, variable_type, expr.span(), scope)?;
+                        format!("{} {bits}", Self::get_data_directive(size))
+                    }
+                    // the length comes first, like on the stack
+                    (None, Some(linja)) => format!("dq {}, __tp_linja_{}", linja.value.len(), scope.add_string(&linja.value)),
+                    (None, None) => {
+                        return Err(Diagnostic::error("E0412", format!("the value of {} is not known at compile time", nimi.value), expr.span())
+                            .with_note("a variable outside of a pali can only start with literals and awen")
+                            .with_note("give it its value in a pali instead"));
+                    }
+                })
+            }
+        };
+
+        writeln!(writer)?;
+        writeln!(writer, "    ; global {variable_type} {}", nimi.value)?;
+        scope.globals.push(Global { name: nimi.value.clone(), size, data, public: osin.public });
+        scope.get_environment_mut().add_global(&nimi.value, variable_type);
+        Ok(())
+    }
+
+    fn generate_li_kama_sama_statement(kama_sama: &LiKamaSamaStatement, scope: &mut Scope, writer: &mut BufWriter<fs::File>) -> Result<(), Diagnostic> {
+        writeln!(writer)?;
+
+        let variable = scope.get_variable(&kama_sama.nimi.value, &kama_sama.nimi.span)?.0;
+        if variable.constant {
+            return Err(Diagnostic::error("E0411", format!("{} is awen and can not be changed", kama_sama.nimi.value), &kama_sama.nimi.span)
+                .with_note(format!("declare it without awen to change it, like o sin e {} {}", variable.type_name, kama_sama.nimi.value)));
+        }
+        let var_type = variable.type_name.clone();
+        if !kama_sama.expression.fits_type(&var_type, scope)? {
+            return Err(Diagnostic::error(
+                "E0404",
+                format!("{} is a {var_type} but its new value is a {}", kama_sama.nimi.value, kama_sama.expression.get_type_name(scope)?),
+                kama_sama.expression.span(),
+            ));
+        }
+        Self::generate_expression_as(&kama_sama.expression, &var_type, scope, writer)?;
+
+        Self::generate_nimi_recieve_stack(&kama_sama.nimi, scope, writer)
+    }
+
+    fn generate_unary_expression(unary: &UnaryExpression, scope: &mut Scope, writer: &mut BufWriter<fs::File>) -> Result<(), Diagnostic> {
+        match unary {
+            UnaryExpression::Nanpa(nanpa) => Self::generate_constant(Constant::Nanpa(nanpa.value), "nanpa", &nanpa.span, scope, writer),
+            UnaryExpression::Telo(telo) => Self::generate_constant(Constant::Telo(telo.value), "telo tu", &telo.span, scope, writer),
+            UnaryExpression::Nimi(nimi) => Self::generate_nimi_expression(nimi, scope, writer),
+            UnaryExpression::O(o) => Self::generate_o(o, scope, writer),
+            UnaryExpression::Linja(linja) => Self::generate_linja_expression(linja, scope, writer),
+            UnaryExpression::Lon(lon) => Self::push(lon.value as i128, scope, writer),
+            UnaryExpression::Ala(ala) => {
+                Self::expect_lon(&ala.expr, "the value after ala", scope)?;
+                Self::generate_expression_as(&ala.expr, "lon", scope, writer)?;
+                Self::pop_reg("r8", scope, writer)?;
+                Self::zero("ecx", writer)?;
+                writeln!(writer, "    cmp r8, 0")?;
+                writeln!(writer, "    sete cl")?;
+                Self::push_reg("rcx", scope, writer)
+            }
+            UnaryExpression::Group(group) => Self::generate_expression(&group.expr, scope, writer),
+            UnaryExpression::Negative(negative) => {
+                let negative_type = Self::get_scalar_type(&negative.expr, scope)?;
+                if negative_type.kind == TypeKind::Lon {
+                    return Err(Diagnostic::error("E0410", "a lon can not be negative", &negative.span)
+                        .with_note("use ala to turn a lon around"));
+                }
+                Self::generate_expression(&negative.expr, scope, writer)?;
+                Self::pop_reg("r8", scope, writer)?;
+                if negative_type.kind == TypeKind::Float {
+                    // flips the sign bit
+                    writeln!(writer, "    btc r8, {}", negative_type.size * 8 - 1)?;
+                } else {
+                    writeln!(writer, "    neg r8")?;
+                    Self::extend("r8", &negative_type, writer)?;
+                }
+                Self::push_reg("r8", scope, writer)
+            }
+        }
+    }
+
+    // en / anu only evaluate the right side when the left one does not decide the result
+    fn generate_logic_expression(binary: &BinaryExpression, scope: &mut Scope, writer: &mut BufWriter<fs::File>) -> Result<(), Diagnostic> {
+        scope.label_counter += 1;
+        let label_index = scope.label_counter;
+        Self::expect_lon(&binary.lhs, "each side of en and anu", scope)?;
+        Self::expect_lon(&binary.rhs, "each side of en and anu", scope)?;
+
+        Self::generate_expression_as(&binary.lhs, "lon", scope, writer)?;
+        Self::pop_reg("r8", scope, writer)?;
+        match binary.kind {
+            BinaryExpressionType::And => {
+                Self::zero("ecx", writer)?;
+                writeln!(writer, "    cmp r8, 0")?;
+                writeln!(writer, "    je .logic_{label_index}")?;
+            }
+            BinaryExpressionType::Or => {
+                Self::mov("rcx", 8, "1", writer)?;
+                writeln!(writer, "    cmp r8, 0")?;
+                writeln!(writer, "    jne .logic_{label_index}")?;
+            }
+            _ => unreachable!("{:?} is not a logic operator", binary.kind),
+        }
+
+        Self::generate_expression_as(&binary.rhs, "lon", scope, writer)?;
+        Self::pop_reg("r8", scope, writer)?;
+        Self::zero("ecx", writer)?;
+        writeln!(writer, "    cmp r8, 0")?;
+        writeln!(writer, "    setne cl")?;
+        writeln!(writer, "  .logic_{label_index}:")?;
+        Self::push_reg("rcx", scope, writer)
+    }
+
+    // the sides are already popped into r8 and r9
+    fn generate_telo_binary_expression(binary: &BinaryExpression, telo: &Type, scope: &mut Scope, writer: &mut BufWriter<fs::File>) -> Result<(), Diagnostic> {
+        let suffix = match telo.size {
+            4 => "ss",
+            _ => "sd",
+        };
+        writeln!(writer, "    movq xmm0, r8")?;
+        writeln!(writer, "    movq xmm1, r9")?;
+
+        let instruction = match binary.kind {
+            BinaryExpressionType::Add => "add",
+            BinaryExpressionType::Subtract => "sub",
+            BinaryExpressionType::Multiply => "mul",
+            BinaryExpressionType::Divide => "div",
+            // a and ae are false when either side is NaN, so < and <= compare the other way around
+            BinaryExpressionType::Equals
+            | BinaryExpressionType::Unequals
+            | BinaryExpressionType::LessThan
+            | BinaryExpressionType::GreaterThan
+            | BinaryExpressionType::LessEquals
+            | BinaryExpressionType::GreaterEquals => {
+                let (lhs, rhs, condition) = match binary.kind {
+                    BinaryExpressionType::LessThan => ("xmm1", "xmm0", "a"),
+                    BinaryExpressionType::LessEquals => ("xmm1", "xmm0", "ae"),
+                    BinaryExpressionType::GreaterThan => ("xmm0", "xmm1", "a"),
+                    BinaryExpressionType::GreaterEquals => ("xmm0", "xmm1", "ae"),
+                    _ => ("xmm0", "xmm1", Self::get_condition_code(binary.kind, false)),
+                };
+                Self::zero("ecx", writer)?;
+                writeln!(writer, "    ucomi{suffix} {lhs}, {rhs}")?;
+                writeln!(writer, "    set{condition} cl")?;
+                // NaN is unordered: never equal, always unequal
+                match binary.kind {
+                    BinaryExpressionType::Equals => {
+                        writeln!(writer, "    setnp al")?;
+                        writeln!(writer, "    and cl, al")?;
+                    }
+                    BinaryExpressionType::Unequals => {
+                        writeln!(writer, "    setp al")?;
+                        writeln!(writer, "    or cl, al")?;
+                    }
+                    _ => {}
+                }
+                return Self::push_reg("rcx", scope, writer);
+            }
+            kind => {
+                return Err(Diagnostic::error("E0404", format!("{kind:?} can not be used with a {}", telo.name), &binary.span)
+                    .with_note("a telo only has + - * / and comparisons"));
+            }
+        };
+
+        writeln!(writer, "    {instruction}{suffix} xmm0, xmm1")?;
+        writeln!(writer, "    movq r8, xmm0")?;
+        Self::push_reg("r8", scope, writer)
+    }
+
+    fn generate_binary_expression(binary: &BinaryExpression, scope: &mut Scope, writer: &mut BufWriter<fs::File>) -> Result<(), Diagnostic> {
+        if matches!(binary.kind, BinaryExpressionType::And | BinaryExpressionType::Or) {
+            return Self::generate_logic_expression(binary, scope, writer);
+        }
+
+        Self::get_scalar_type(&binary.lhs, scope)?;
+        Self::get_scalar_type(&binary.rhs, scope)?;
+        // both sides are already extended to a qword, so a narrower one
+        // can be calculated with as the wider type directly
+        let operand_type_name = binary.get_operand_type_name(scope)?;
+        let binary_type = scope.get_type(&operand_type_name, &binary.span)?;
+        Self::generate_expression_as(&binary.lhs, &operand_type_name, scope, writer)?;
+        Self::generate_expression_as(&binary.rhs, &operand_type_name, scope, writer)?;
+        Self::pop_reg("r9", scope, writer)?;
+        Self::pop_reg("r8", scope, writer)?;
+
+        if binary_type.kind == TypeKind::Float {
+            return Self::generate_telo_binary_expression(binary, &binary_type, scope, writer);
+        }
+
+        // lon values can only be compared
+        if binary_type.kind == TypeKind::Lon
+            && !matches!(binary.kind, BinaryExpressionType::Equals | BinaryExpressionType::Unequals)
+        {
+            return Err(Diagnostic::error("E0410", format!("{:?} can not be used with a lon", binary.kind), &binary.span)
+                .with_note("a lon only has == and !=, and en, anu and ala to combine it"));
+        }
+
+        match binary.kind {
+            BinaryExpressionType::Add => {
+                writeln!(writer, "    add r8, r9")?;
+                Self::extend("r8", &binary_type, writer)?;
+                Self::push_reg("r8", scope, writer)?;
+            }
+            BinaryExpressionType::Subtract => {
+                writeln!(writer, "    sub r8, r9")?;
+                Self::extend("r8", &binary_type, writer)?;
+                Self::push_reg("r8", scope, writer)?;
+            }
+            BinaryExpressionType::Multiply if binary_type.signed => {
+                writeln!(writer, "    imul r8, r9")?;
+                Self::extend("r8", &binary_type, writer)?;
+                Self::push_reg("r8", scope, writer)?;
+            }
+            BinaryExpressionType::Multiply => {
+                Self::mov("rax", 8, "r8", writer)?;
+                writeln!(writer, "    mul r9")?;
+                Self::extend("rax", &binary_type, writer)?;
+                Self::push_reg("rax", scope, writer)?;
+            }
+            // the quotient ends up in rax and the remainder in rdx
+            BinaryExpressionType::Divide | BinaryExpressionType::Modulo => {
+                Self::mov("rax", 8, "r8", writer)?;
+                if binary_type.signed {
+                    writeln!(writer, "    cqo")?;
+                    writeln!(writer, "    idiv r9")?;
+                } else {
+                    Self::zero("rdx", writer)?;
+                    writeln!(writer, "    div r9")?;
+                }
+                let result = match binary.kind {
+                    BinaryExpressionType::Divide => "rax",
+                    _ => "rdx",
+                };
+                Self::extend(result, &binary_type, writer)?;
+                Self::push_reg(result, scope, writer)?;
+            }
+            BinaryExpressionType::BitAnd => {
+                writeln!(writer, "    and r8, r9")?;
+                Self::extend("r8", &binary_type, writer)?;
+                Self::push_reg("r8", scope, writer)?;
+            }
+            BinaryExpressionType::BitOr => {
+                writeln!(writer, "    or r8, r9")?;
+                Self::extend("r8", &binary_type, writer)?;
+                Self::push_reg("r8", scope, writer)?;
+            }
+            BinaryExpressionType::BitXor => {
+                writeln!(writer, "    xor r8, r9")?;
+                Self::extend("r8", &binary_type, writer)?;
+                Self::push_reg("r8", scope, writer)?;
+            }
+            // the shift count has to be in cl
+            BinaryExpressionType::ShiftLeft => {
+                Self::mov("rcx", 8, "r9", writer)?;
+                writeln!(writer, "    shl r8, cl")?;
+                Self::extend("r8", &binary_type, writer)?;
+                Self::push_reg("r8", scope, writer)?;
+            }
+            // signed values keep their sign, unsigned ones are filled with zeros
+            BinaryExpressionType::ShiftRight => {
+                let instruction = if binary_type.signed { "sar" } else { "shr" };
+                Self::mov("rcx", 8, "r9", writer)?;
+                writeln!(writer, "    {instruction} r8, cl")?;
+                Self::extend("r8", &binary_type, writer)?;
+                Self::push_reg("r8", scope, writer)?;
+            }
+            BinaryExpressionType::Equals
+            | BinaryExpressionType::Unequals
+            | BinaryExpressionType::LessThan
+            | BinaryExpressionType::GreaterThan
+            | BinaryExpressionType::LessEquals
+            | BinaryExpressionType::GreaterEquals => {
+                let condition = Self::get_condition_code(binary.kind, binary_type.signed);
+                Self::zero("ecx", writer)?;
+                writeln!(writer, "    cmp r8, r9")?;
+                writeln!(writer, "    set{condition} cl")?;
+                Self::push_reg("rcx", scope, writer)?;
+            }
+            BinaryExpressionType::And | BinaryExpressionType::Or => unreachable!(),
+        }
+        Ok(())
+    }
+
+    fn generate_expression(expression: &Expression, scope: &mut Scope, writer: &mut BufWriter<fs::File>) -> Result<(), Diagnostic> {
+        match expression {
+            Expression::Unary(unary) => Self::generate_unary_expression(unary, scope, writer),
+            Expression::Binary(binary) => Self::generate_binary_expression(binary, scope, writer),
+        }
+    }
+
+    // for an expression already checked with `fits_type`, so number constants get that type
+    fn generate_expression_as(expression: &Expression, type_name: &str, scope: &mut Scope, writer: &mut BufWriter<fs::File>) -> Result<(), Diagnostic> {
+        let span = expression.span();
+        match expression.evaluate(Some(scope)) {
+            Some(constant) => Self::generate_constant(constant, type_name, span, scope, writer),
+            None => Self::generate_expression(expression, scope, writer),
+        }
+    }
+
+    fn generate_otawa(otawa: &OtawaStatement, scope: &mut Scope, writer: &mut BufWriter<fs::File>) -> Result<(), Diagnostic> {
+	Self::get_scalar_type(&otawa.expr, scope)?;
+        Self::generate_expression(&otawa.expr, scope, writer)?;
+        writeln!(writer)?;
+        writeln!(writer, "    ; Exit call:")?;
+        Self::pop_reg("rdi", scope, writer)?;
+        Self::mov("rax", 8, "60", writer)?;
+        writeln!(writer, "    syscall")?;
+        writeln!(writer)?;
+        Ok(())
     }
 
     fn generate_parameter(
         param: &(String, NimiExpression),
         scope: &mut Scope,
-        offset: usize,
+        registers: &[String],
 	writer: &mut BufWriter<fs::File>
-    ) {
-        writeln!(writer, "    ; Setting parameter {} of type {}", param.1.value, param.0);
-        scope.add_variable(&param.1.value, param.0.as_str(), Some(&Self::get_argument_register(offset)), writer);
+    ) -> Result<(), Diagnostic> {
+        writeln!(writer, "    ; Setting parameter {} of type {}", param.1.value, param.0)?;
+        scope.add_variable(&param.1.value, param.0.as_str(), &param.1.span, Some(registers), writer)?;
+        Ok(())
     }
 
-    fn generate_o(o: &OExpression, scope: &mut Scope, writer: &mut BufWriter<fs::File>) {
-        let func = scope.get_function(&o.nimi.value).unwrap();
+    fn generate_o(o: &OExpression, scope: &mut Scope, writer: &mut BufWriter<fs::File>) -> Result<(), Diagnostic> {
+        let func = scope.get_function(&o.nimi.value, &o.nimi.span)?;
 
         let types = func.parameter_types.clone();
         let return_type = func.return_type.clone();
 
-        writeln!(writer, );
-        writeln!(writer, "    ; o {}", o.nimi.value);
-
-        if (&o.params)
-            .into_iter()
-            .map(|v| return v.get_type_name(scope))
-            .collect::<Vec<_>>()
-            != types
-        {
-            panic!("caller arguments do not match function parameters");
+        writeln!(writer)?;
+        writeln!(writer, "    ; o {}", o.nimi.value)?;
+
+        let argument_types = o
+            .params
+            .iter()
+            .map(|v| v.get_type_name(scope))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let fits = o
+            .params
+            .iter()
+            .zip(&types)
+            .map(|(param, type_name)| param.fits_type(type_name, scope))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if argument_types.len() != types.len() || fits.contains(&false) {
+            return Err(Diagnostic::error(
+                "E0404",
+                "caller arguments do not match function parameters",
+                &o.span,
+            )
+            .with_note(format!(
+                "{} expects ({}), but was given ({})",
+                o.nimi.value,
+                types.join(", "),
+                argument_types.join(", ")
+            )));
         }
 
-        for (index, expr) in o.params.iter().enumerate().rev() {
-            Self::generate_expression(expr, scope, writer);
-	    let size = scope.get_type(&expr.get_type_name(scope)).unwrap().size;
-	    Self::pop_reg(&Self::get_argument_register(index), size, scope, writer);
+        // a linja argument takes two registers, so they are handed out before evaluating
+        let parameter_types = types
+            .iter()
+            .map(|type_name| scope.get_type(type_name, &o.span))
+            .collect::<Result<Vec<_>, _>>()?;
+        let registers = Self::get_argument_registers(&parameter_types);
+
+        // every argument is evaluated before any register is filled,
+        // since evaluating one may use the registers of another
+        for (index, expr) in o.params.iter().enumerate() {
+            Self::generate_expression_as(expr, &types[index], scope, writer)?;
+        }
+        for registers in registers.iter().rev() {
+	    Self::pop_regs(registers, scope, writer)?;
         }
 
-        writeln!(writer, "    call {}", o.nimi.value);
+        writeln!(writer, "    call {}", o.nimi.value)?;
 
-        if return_type.is_some() {
-	    let size = scope.get_type(&return_type.unwrap()).unwrap().size;
-            Generator::push_reg("rax", size, scope, writer);
+        if let Some(return_type) = return_type {
+	    let return_type = scope.get_type(&return_type, &o.span)?;
+            Generator::push_regs(&Self::get_return_registers(&return_type), scope, writer)?;
         };
+        Ok(())
     }
 
-    fn generate_o_weka(oweka: &OWekaStatement, scope: &mut Scope, writer: &mut BufWriter<fs::File>) {
-        match &oweka.expr {
-            None => {}
-            Some(expr) => {
-		let size = scope.get_type(&expr.get_type_name(scope)).unwrap().size;
-                Self::generate_expression(&expr, scope, writer);
-                Self::pop_reg("rax", size, scope, writer);
+    fn generate_o_weka(oweka: &OWekaStatement, scope: &mut Scope, writer: &mut BufWriter<fs::File>) -> Result<(), Diagnostic> {
+        match (scope.return_type.clone(), &oweka.expr) {
+            (Some(type_name), Some(expr)) => {
+                if !expr.fits_type(&type_name, scope)? {
+                    return Err(Diagnostic::error(
+                        "E0404",
+                        format!("this returns a {} but the pali gives back a {type_name}", expr.get_type_name(scope)?),
+                        expr.span(),
+                    ));
+                }
+                let return_type = scope.get_type(&type_name, expr.span())?;
+                Self::generate_expression_as(expr, &type_name, scope, writer)?;
+                Self::pop_regs(&Self::get_return_registers(&return_type), scope, writer)?;
             }
+            (None, Some(expr)) => {
+                return Err(Diagnostic::error("E0414", "this pali gives nothing back, so o weka can not take a value", expr.span())
+                    .with_note("add 'li pana e' and a type to the pali to give back a value"));
+            }
+            (Some(type_name), None) if !oweka.implicit => {
+                return Err(Diagnostic::error("E0414", format!("this pali gives back a {type_name}, so o weka needs a value"), &oweka.span)
+                    .with_note(format!("write o weka e and a {type_name}")));
+            }
+            (_, None) => {}
         }
 
-        writeln!(writer, "    ; returning");
-        Self::mov("rsp", 8, "rbp", writer);
-        writeln!(writer, "    pop rbp");
-        writeln!(writer, "    ret");
+        writeln!(writer, "    ; returning")?;
+        Self::mov("rsp", 8, "rbp", writer)?;
+        writeln!(writer, "    pop rbp")?;
+        writeln!(writer, "    ret")?;
+        Ok(())
     }
 
-    fn generate_tenpo(tenpo: &TenpoStatement, scope: &mut Scope, writer: &mut BufWriter<fs::File>) {
+    fn generate_tenpo(tenpo: &TenpoStatement, scope: &mut Scope, writer: &mut BufWriter<fs::File>) -> Result<(), Diagnostic> {
         scope.label_counter += 1;
         let label_index = scope.label_counter;
-        writeln!(writer, "  ; tenpo .. la");
-	Self::new_scope(scope, writer);
-        Self::generate_expression(&tenpo.expr, scope, writer);
-	let size = scope.get_type(&tenpo.expr.get_type_name(scope)).unwrap().size;
-        Self::pop_reg("rax", size, scope, writer);
-        writeln!(writer, "    cmp rax, 0");
-        writeln!(writer, "    je .endif_{label_index}");
-	
+        writeln!(writer, "  ; tenpo .. la")?;
+	Self::new_scope(scope, writer)?;
+	Self::expect_lon(&tenpo.expr, "the condition of tenpo pi", scope)?;
+        Self::generate_expression_as(&tenpo.expr, "lon", scope, writer)?;
+        Self::pop_reg("rax", scope, writer)?;
+        writeln!(writer, "    cmp rax, 0")?;
+        match tenpo.ante {
+            Some(_) => writeln!(writer, "    je .else_{label_index}")?,
+            None => writeln!(writer, "    je .endif_{label_index}")?,
+        }
+
         for node in &tenpo.nodes {
-            if Self::generate_node(node, scope, writer) {
+            if Self::generate_node(node, scope, writer)? {
                 break;
             }
         }
-        writeln!(writer, 
+        writeln!(writer,
             "    add rsp, {}",
-            scope.get_environment_mut().names.len() * 8
-        );
+            scope.get_environment().stack_pointer
+        )?;
+
+	Self::end_scope(scope, writer)?;
+
+        if let Some(ante) = &tenpo.ante {
+            writeln!(writer, "    jmp .endif_{label_index}")?;
+            writeln!(writer, "  .else_{label_index}:")?;
+
+            match ante {
+                AnteStatement::Tenpo(tenpo) => Self::generate_tenpo(tenpo, scope, writer)?,
+                AnteStatement::La(nodes) => {
+                    writeln!(writer, "  ; ante la")?;
+                    Self::new_scope(scope, writer)?;
+                    for node in nodes {
+                        if Self::generate_node(node, scope, writer)? {
+                            break;
+                        }
+                    }
+                    writeln!(writer,
+                        "    add rsp, {}",
+                        scope.get_environment().stack_pointer
+                    )?;
+                    Self::end_scope(scope, writer)?;
+                }
+            }
+        }
 
-	Self::end_scope(scope, writer);
-	
-        writeln!(writer, "  .endif_{label_index}:").unwrap();
+        writeln!(writer, "  .endif_{label_index}:")?;
+        Ok(())
     }
 
-    fn generate_pali_declaration(pali: &PaliDeclaration, scope: &mut Scope, writer: &mut BufWriter<fs::File>) {
-	scope.declare_function(pali);
+    // the condition is checked before every iteration, and whatever the body
+    // put on the stack is dropped before jumping back to it
+    fn generate_tenpo_ale(tenpo_ale: &TenpoAleStatement, scope: &mut Scope, writer: &mut BufWriter<fs::File>) -> Result<(), Diagnostic> {
+        scope.label_counter += 1;
+        let label_index = scope.label_counter;
+        writeln!(writer, "  ; tenpo ale .. la")?;
+        writeln!(writer, "  .loop_{label_index}:")?;
+	Self::new_scope(scope, writer)?;
+	Self::expect_lon(&tenpo_ale.expr, "the condition of tenpo ale pi", scope)?;
+        Self::generate_expression_as(&tenpo_ale.expr, "lon", scope, writer)?;
+        Self::pop_reg("rax", scope, writer)?;
+        writeln!(writer, "    cmp rax, 0")?;
+        writeln!(writer, "    je .endloop_{label_index}")?;
+
+        for node in &tenpo_ale.nodes {
+            if Self::generate_node(node, scope, writer)? {
+                break;
+            }
+        }
+        writeln!(writer,
+            "    add rsp, {}",
+            scope.get_environment().stack_pointer
+        )?;
+
+	Self::end_scope(scope, writer)?;
 
-	writeln!(writer, "extrn {0}", pali.nimi.value).unwrap();
+        writeln!(writer, "    jmp .loop_{label_index}")?;
+        writeln!(writer, "  .endloop_{label_index}:")?;
+        Ok(())
     }
-    
-    fn generate_pali(pali: &PaliStatement, scope: &mut Scope, writer: &mut BufWriter<fs::File>) {
-        scope.add_function(pali);
 
-        writeln!(writer, "public {}", pali.nimi.value).unwrap();
-        writeln!(writer, "{}:", pali.nimi.value).unwrap();
-        Self::new_scope(scope, writer);
-        Self::push_reg("rbp", 8, scope, writer);
-        writeln!(writer, "    mov rbp, rsp").unwrap();
+    fn generate_pali_declaration(pali: &PaliDeclaration, scope: &mut Scope, writer: &mut BufWriter<fs::File>) -> Result<(), Diagnostic> {
+	scope.declare_function(pali);
 
-        if !pali.params.is_empty() {
-            let mut offset = 0;
-            for param in &pali.params {
-                Self::generate_parameter(param, scope, offset, writer);
-                offset += 1;
-            }
+	writeln!(writer, "extrn {0}", pali.nimi.value)?;
+	Ok(())
+    }
+
+    fn generate_pali(pali: &PaliStatement, scope: &mut Scope, writer: &mut BufWriter<fs::File>) -> Result<(), Diagnostic> {
+        scope.add_function(pali);
+        scope.return_type = pali.retval.clone();
+
+        writeln!(writer, "public {}", pali.nimi.value)?;
+        writeln!(writer, "{}:", pali.nimi.value)?;
+        writeln!(writer, "    push rbp")?;
+        writeln!(writer, "    mov rbp, rsp")?;
+        Self::new_scope(scope, writer)?;
+
+        let types = pali
+            .params
+            .iter()
+            .map(|param| scope.get_type(&param.0, &param.1.span))
+            .collect::<Result<Vec<_>, _>>()?;
+        let registers = Self::get_argument_registers(&types);
+        for (param, registers) in pali.params.iter().zip(&registers) {
+            Self::generate_parameter(param, scope, registers, writer)?;
         }
 
         for node in &pali.nodes {
-            if Self::generate_node(node, scope, writer) {
+            if Self::generate_node(node, scope, writer)? {
                 break;
             };
         }
-	
-	Self::end_scope(scope, writer);
+
+	Self::end_scope(scope, writer)
     }
 
-    fn new_scope(scope: &mut Scope, writer: &mut BufWriter<fs::File>) {
-        writeln!(writer, "  ; new scope");
+    fn new_scope(scope: &mut Scope, writer: &mut BufWriter<fs::File>) -> Result<(), Diagnostic> {
+        writeln!(writer, "  ; new scope")?;
         scope.envs.push(Environment {
             names: HashMap::new(),
             stack_pointer: 0,
             tab_depth: 0,
         });
+        Ok(())
     }
 
-    fn end_scope(scope: &mut Scope, writer: &mut BufWriter<fs::File>) {
+    fn end_scope(scope: &mut Scope, writer: &mut BufWriter<fs::File>) -> Result<(), Diagnostic> {
         scope.envs.pop();
-        writeln!(writer, "  ; end of scope");
-	writeln!(writer, );
+        writeln!(writer, "  ; end of scope")?;
+	writeln!(writer)?;
+	Ok(())
     }
 
-    fn generate_parenthesis(paren: &Parenthesis, scope: &mut Scope, writer: &mut BufWriter<fs::File>) {
-        writeln!(writer, );
-        Self::new_scope(scope, writer);
+    fn generate_parenthesis(paren: &Parenthesis, scope: &mut Scope, writer: &mut BufWriter<fs::File>) -> Result<(), Diagnostic> {
+        writeln!(writer)?;
+        Self::new_scope(scope, writer)?;
 
         for node in &paren.nodes {
-            Self::generate_node(node, scope, writer);
+            Self::generate_node(node, scope, writer)?;
         }
 
-        writeln!(writer, 
+        writeln!(writer,
             "    add rsp, {}",
-            scope.get_environment_mut().names.len() * 8
-        );
-	Self::end_scope(scope, writer);
+            scope.get_environment().stack_pointer
+        )?;
+	Self::end_scope(scope, writer)
     }
 
-    fn generate_node(node: &Node, scope: &mut Scope, writer: &mut BufWriter<fs::File>) -> bool {
+    // returns whether the node leaves the current block
+    fn generate_node(node: &Node, scope: &mut Scope, writer: &mut BufWriter<fs::File>) -> Result<bool, Diagnostic> {
         match node {
             Node::Otawa(otawa) => {
-                Self::generate_otawa(otawa, scope, writer);
-                return true;
-            }
-            Node::Tenpo(tenpo) => Self::generate_tenpo(tenpo, scope, writer),
-            Node::Expression(expression) => Self::generate_expression(expression, scope, writer),
-            Node::OSin(osin) => Self::generate_o_sin(osin, scope, writer),
-            Node::LiKamaSama(kamasama) => Self::generate_li_kama_sama_statement(kamasama, scope, writer),
-            Node::Pali(pali) => Self::generate_pali(pali, scope, writer),
-	    Node::PaliDeclaration(pali) => Self::generate_pali_declaration(pali, scope, writer),
+                Self::generate_otawa(otawa, scope, writer)?;
+                return Ok(true);
+            }
+            Node::Tenpo(tenpo) => Self::generate_tenpo(tenpo, scope, writer)?,
+            Node::TenpoAle(tenpo_ale) => Self::generate_tenpo_ale(tenpo_ale, scope, writer)?,
+            Node::Expression(expression) => Self::generate_expression(expression, scope, writer)?,
+            Node::OSin(osin) => Self::generate_o_sin(osin, scope, writer)?,
+            Node::LiKamaSama(kamasama) => Self::generate_li_kama_sama_statement(kamasama, scope, writer)?,
+            Node::Pali(pali) => Self::generate_pali(pali, scope, writer)?,
+	    Node::PaliDeclaration(pali) => Self::generate_pali_declaration(pali, scope, writer)?,
             Node::OWeka(oweka) => {
-                Self::generate_o_weka(oweka, scope, writer);
-                return true;
+                Self::generate_o_weka(oweka, scope, writer)?;
+                return Ok(true);
             }
             Node::O(o) => {
-                Self::generate_o(o, scope, writer);
-		let func = scope.get_function(&o.nimi.value).unwrap();
-		if func.return_type.is_some()  {
-		    let size = scope.get_type(&func.return_type.clone().unwrap()).unwrap().size;
-		    Self::pop_reg("rax", size, scope, writer);
+                Self::generate_o(o, scope, writer)?;
+		let func = scope.get_function(&o.nimi.value, &o.nimi.span)?;
+		if let Some(return_type) = func.return_type.clone() {
+		    let return_type = scope.get_type(&return_type, &o.span)?;
+		    Self::pop_regs(&Self::get_return_registers(&return_type), scope, writer)?;
 		}
             }
-            Node::Parenthesis(paren) => Self::generate_parenthesis(paren, scope, writer),
+            Node::Parenthesis(paren) => Self::generate_parenthesis(paren, scope, writer)?,
+            Node::Asenpeli(asen) => Self::generate_asen(asen, scope, writer)?,
+	    Node::Kepeken(kepeken) => Self::generate_kepeken(kepeken, scope, writer)?,
             _ => {}
         };
-        false
+        Ok(false)
     }
 
-    fn generate_prelude(&mut self, writer: &mut BufWriter<fs::File>) {
-	writeln!(writer, "format ELF64");
+    fn generate_prelude(&mut self, writer: &mut BufWriter<fs::File>) -> Result<(), Diagnostic> {
+	writeln!(writer, "format ELF64")?;
+	writeln!(writer, "section '.text' executable")?;
+	Ok(())
     }
-    
-    fn generate(&mut self, scope: &mut Scope, writer: &mut BufWriter<fs::File>) {
-        self.generate_prelude(writer);
+
+    // string literals, zero terminated so they can also be handed to C, and the awen of the file
+    fn generate_rodata(scope: &Scope, writer: &mut BufWriter<fs::File>) -> Result<(), Diagnostic> {
+	if scope.strings.is_empty() && scope.constants.is_empty() {
+	    return Ok(());
+	}
+
+	writeln!(writer)?;
+	writeln!(writer, "section '.rodata'")?;
+	for (index, string) in scope.strings.iter().enumerate() {
+	    let bytes: Vec<String> = string.bytes().chain([0]).map(|byte| byte.to_string()).collect();
+	    writeln!(writer, "__tp_linja_{index} db {}", bytes.join(", "))?;
+	}
+	for constant in &scope.constants {
+	    if constant.public {
+		writeln!(writer, "public {}", constant.name)?;
+	    }
+	    writeln!(writer, "{} {} {}", constant.name, Self::get_data_directive(constant.size), constant.bits)?;
+	}
+	Ok(())
+    }
+
+    // globals with a value go to .data, the rest to .bss where the loader zeroes them
+    fn generate_globals(scope: &Scope, writer: &mut BufWriter<fs::File>) -> Result<(), Diagnostic> {
+	let (data, bss): (Vec<&Global>, Vec<&Global>) = scope.globals.iter().partition(|global| global.data.is_some());
+	if !data.is_empty() {
+	    writeln!(writer)?;
+	    writeln!(writer, "section '.data' writeable align 8")?;
+	    for global in data {
+		writeln!(writer, "align 8")?;
+		if global.public {
+		    writeln!(writer, "public {}", global.name)?;
+		}
+		writeln!(writer, "{} {}", global.name, global.data.as_deref().unwrap_or_default())?;
+	    }
+	}
+	if !bss.is_empty() {
+	    writeln!(writer)?;
+	    writeln!(writer, "section '.bss' writeable align 8")?;
+	    for global in bss {
+		writeln!(writer, "align 8")?;
+		if global.public {
+		    writeln!(writer, "public {}", global.name)?;
+		}
+		writeln!(writer, "{} rb {}", global.name, global.size)?;
+	    }
+	}
+	Ok(())
+    }
+
+    fn generate(&mut self, scope: &mut Scope, writer: &mut BufWriter<fs::File>) -> Result<(), Diagnostic> {
+        self.generate_prelude(writer)?;
         for node in &self.nodes {
-            if Self::generate_node(node, scope, writer) {
+            if Self::generate_node(node, scope, writer)? {
                 break;
             };
         }
+        Self::generate_rodata(scope, writer)?;
+        Self::generate_globals(scope, writer)
     }
-    
+
 }
 
 #[derive(Eq, PartialEq)]
@@ -1906,36 +4098,169 @@ enum RunMode {
     Linked
 }
 
-fn main() {
+// runs an external tool and turns a failure into a diagnostic
+fn run_command(command: &mut Command, name: &str) -> Result<(), Diagnostic> {
+    let output = command.output().map_err(|err| {
+        Diagnostic::error_without_span("E0503", format!("could not run {name}: {err}"))
+    })?;
+
+    if !output.status.success() {
+        let mut diagnostic =
+            Diagnostic::error_without_span("E0504", format!("{name} failed with {}", output.status));
+        for line in String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .chain(String::from_utf8_lossy(&output.stderr).lines())
+        {
+            diagnostic = diagnostic.with_note(line.to_string());
+        }
+        return Err(diagnostic);
+    }
+    Ok(())
+}
+
+fn run(args: &[String]) -> Result<(), Vec<Diagnostic>> {
     let debug_mode = false;
 
-    let args: Vec<String> = env::args().collect();
+    let usage = "usage: tpc <o|l> <input.tp> <output>";
 
-    let mode = match args.get(1).unwrap().as_str() {
-	"o" => RunMode::Object,
-	"l" => RunMode::Linked,
-	_ => panic!("not a valid mode"),
+    let mode = match args.get(1).map(|mode| mode.as_str()) {
+	Some("o") => RunMode::Object,
+	Some("l") => RunMode::Linked,
+	_ => return Err(vec![Diagnostic::error_without_span("E0502", "not a valid mode").with_note(usage)]),
     };
 
+    let (input_file, output_file) = match (args.get(2), args.get(3)) {
+	(Some(input_file), Some(output_file)) => (input_file, output_file),
+	_ => return Err(vec![Diagnostic::error_without_span("E0502", "missing file arguments").with_note(usage)]),
+    };
 
-    let input_file = args.get(2).unwrap();
-    
-    let input = fs::read_to_string(input_file).expect("no input file");
+    let scope = compile(input_file, output_file, debug_mode)?;
 
-    let output_file = args.get(3).unwrap();
+    run_command(Command::new("fasm").arg(output_file.clone() + ".asm"), "fasm")?;
+
+    if mode == RunMode::Linked {
+	let mut objects = vec![output_file.clone() + ".o", "lib/asen_asm.o".to_string()];
+	objects.extend(scope.modules.iter().map(|module| format!("lib/{module}.o")));
+	run_command(Command::new("ld").args(objects), "ld")?;
+
+	Command::new("mov").args([
+	    "a.out".to_string(),
+	    output_file.to_string()
+	]);
+    }
+    Ok(())
+}
+
+// writes the assembly of the input file to 'output'.asm, the scope that is
+// handed back knows the modules that still have to be linked in
+fn compile(input_file: &str, output_file: &str, debug_mode: bool) -> Result<Scope, Vec<Diagnostic>> {
+    let (nodes, diagnostics) = parse_file(input_file, debug_mode);
+    if !diagnostics.is_empty() {
+        return Err(diagnostics);
+    }
+
+    let output = fs::File::create(output_file.to_string() + ".asm").map_err(|err| {
+        Diagnostic::error_without_span("E0501", format!("could not create {output_file}.asm: {err}"))
+    })?;
+
+    let mut generator = Generator {
+        nodes,
+    };
+
+    // modules are looked up next to the input file first
+    let mut search_path = vec![match Path::new(input_file).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    }];
+    if let Some(paths) = env::var_os("TP_PATH") {
+        search_path.extend(env::split_paths(&paths));
+    }
+
+    let mut scope = Scope {
+        envs: Vec::new(),
+        functions: HashMap::new(),
+        label_counter: 0,
+	types: HashMap::new(),
+        search_path,
+        modules: Vec::new(),
+        strings: Vec::new(),
+        constants: Vec::new(),
+        globals: Vec::new(),
+        return_type: None,
+    };
+    // sitelen is a unicode character, so it has no signed version
+    for (name, size, signed, kind) in [
+	("nanpa", 8, true, TypeKind::Integer),
+	("nanpa unsigned", 8, false, TypeKind::Integer),
+	("suli", 2, true, TypeKind::Integer),
+	("suli unsigned", 2, false, TypeKind::Integer),
+	("lili", 1, true, TypeKind::Integer),
+	("lili unsigned", 1, false, TypeKind::Integer),
+	("sitelen", 4, false, TypeKind::Integer),
+	("telo", 4, true, TypeKind::Float),
+	("telo tu", 8, true, TypeKind::Float),
+	("lon", 1, false, TypeKind::Lon),
+    ] {
+	scope.types.insert(name.to_string(),
+		Rc::new(Type{
+		    name: name.to_string(),
+		    size,
+		    signed,
+		    kind,
+		}));
+    }
+    scope.types.insert("linja".to_string(),
+	    Rc::new(Type{
+		name: "linja".to_string(),
+		size: 16,
+		signed: false,
+		kind: TypeKind::Linja,
+	    }));
+
+
+    scope.envs.push(Environment {
+        names: HashMap::new(),
+        stack_pointer: 0,
+        tab_depth: 0,
+    });
+
+    let mut writer = BufWriter::new(output);
+    generator.generate(&mut scope, &mut writer)?;
+    writer.flush().map_err(Diagnostic::from)?;
+    Ok(scope)
+}
+
+// lexes, tokenizes and parses a single source file into the nodes that
+// could be parsed, together with every error found on the way
+fn parse_file(input_file: &str, debug_mode: bool) -> (Vec<Node>, Vec<Diagnostic>) {
+    let input = match fs::read_to_string(input_file) {
+        Ok(input) => input,
+        Err(err) => {
+            return (Vec::new(), vec![Diagnostic::error_without_span("E0501", format!("could not read {input_file}: {err}"))]);
+        }
+    };
 
-    let mut output = fs::File::create((*output_file).clone()+".asm").unwrap();
-    
     let mut lexer = Lexer {
         current_position: 0,
+        file: Rc::from(input_file),
         buffer: input,
+        line: 1,
+        column: 1,
         debug_mode,
     };
 
+    let words = match lexer.lex() {
+        Ok(words) => words,
+        Err(err) => return (Vec::new(), vec![err]),
+    };
     let mut abstracter = Abstracter {
-        words: lexer.lex(),
+        token_span: lexer.span(),
+        token_start: 0,
+        number_system: NumberSystem::Pona,
+        words,
         current_word: 0,
         tokens: Vec::new(),
+        diagnostics: Vec::new(),
         debug_mode,
     };
 
@@ -1945,6 +4270,8 @@ fn main() {
         current_token: 0,
         tokens: abstracter.tokens,
         nodes: Vec::new(),
+        diagnostics: Vec::new(),
+        paren_blocks: 0,
         debug_mode,
     };
 
@@ -1956,46 +4283,347 @@ fn main() {
         }
     }
 
-    let mut generator = Generator {
-        nodes: parser.nodes,
-        current_node: 0,
+    let mut diagnostics = abstracter.diagnostics;
+    diagnostics.append(&mut parser.diagnostics);
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span.as_ref().map(|span| (span.line, span.column)));
+    (parser.nodes, diagnostics)
+}
 
-    };
+fn main() {
+    let args: Vec<String> = env::args().collect();
 
-    
-    let mut scope = Scope {
-        envs: Vec::new(),
-        functions: HashMap::new(),
-        label_counter: 0,
-	types: HashMap::new()
-    };
-    scope.types.insert("nanpa".to_string(),
-	    Rc::new(Type{
-		name: "nanpa".to_string(),
-		size: 8
-	    }));
+    if let Err(diagnostics) = run(&args) {
+        for diagnostic in &diagnostics {
+            // the snippet is read back from the file the span points into
+            let source = diagnostic
+                .span
+                .as_ref()
+                .and_then(|span| fs::read_to_string(&*span.file).ok());
+            eprintln!("{}", diagnostic.render(source.as_deref()));
+        }
+        if diagnostics.len() > 1 {
+            eprintln!("error: aborting due to {} previous errors", diagnostics.len());
+        }
+        process::exit(1);
+    }
+}
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn lexer(source: &str) -> Lexer {
+        Lexer {
+            current_position: 0,
+            file: Rc::from("test.tp"),
+            buffer: source.to_string(),
+            line: 1,
+            column: 1,
+            debug_mode: false,
+        }
+    }
 
-    scope.envs.push(Environment {
-        names: HashMap::new(),
-        stack_pointer: 0,
-        tab_depth: 0,
-    });
+    fn lex(source: &str) -> Result<Vec<Word>, Diagnostic> {
+        Ok(lexer(source).lex()?.into_iter().map(|word| word.value).collect())
+    }
 
-    generator.generate(&mut scope, &mut BufWriter::new(output));
-    
-    Command::new("fasm").arg((*output_file).clone()+".asm").output().unwrap();
+    fn tokenize(source: &str) -> Abstracter {
+        let mut lexer = lexer(source);
+        let mut abstracter = Abstracter {
+            token_span: lexer.span(),
+            token_start: 0,
+            number_system: NumberSystem::Pona,
+            words: lexer.lex().unwrap(),
+            current_word: 0,
+            tokens: Vec::new(),
+            diagnostics: Vec::new(),
+            debug_mode: false,
+        };
+        abstracter.tokenize();
+        abstracter
+    }
 
-    if mode == RunMode::Linked {
-	Command::new("ld").args(&[
-	    (*output_file).clone()+".o",
-	    "lib/asen_asm.o".to_string(),
-	    "lib/pu.o".to_string()
-	]).output().unwrap();
-	
-	Command::new("mov").args(&[
-	    "a.out".to_string(),
-	    output_file.to_string()
-	]);
+    // the first number token of the source
+    fn tokenize_number(source: &str) -> Result<String, Vec<Diagnostic>> {
+        let abstracter = tokenize(source);
+        if !abstracter.diagnostics.is_empty() {
+            return Err(abstracter.diagnostics);
+        }
+        abstracter
+            .tokens
+            .into_iter()
+            .find_map(|token| match token.value {
+                Token::Number(number) => Some(number),
+                _ => None,
+            })
+            .ok_or_else(Vec::new)
+    }
+
+    fn parse_expression(source: &str) -> Expression {
+        let abstracter = tokenize(source);
+        assert!(abstracter.diagnostics.is_empty());
+        let mut parser = Parser {
+            current_token: 0,
+            tokens: abstracter.tokens,
+            nodes: Vec::new(),
+            diagnostics: Vec::new(),
+            paren_blocks: 0,
+            debug_mode: false,
+        };
+        parser.parse_expression(Precedence::Undefined).unwrap()
+    }
+
+    // the assembly `tpc o` writes for the source, every test gets its own directory
+    fn compile_source(source: &str) -> Result<String, Vec<Diagnostic>> {
+        let test = thread::current().name().unwrap_or("test").replace("::", "_");
+        let dir = env::temp_dir().join(format!("tpc-{}-{test}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("input.tp");
+        let output = dir.join("output");
+        fs::write(&input, source).unwrap();
+        compile(&input.to_string_lossy(), &output.to_string_lossy(), false)?;
+        Ok(fs::read_to_string(dir.join("output.asm")).unwrap())
+    }
+
+    fn compile_error(source: &str) -> &'static str {
+        compile_source(source).unwrap_err()[0].code
+    }
+
+    // the assembly without comments and indentation, one instruction or label per line
+    fn instructions(asm: &str) -> Vec<String> {
+        asm.lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with(';'))
+            .map(|line| line.to_string())
+            .collect()
+    }
+
+    // whether the lines come one right after the other somewhere in the assembly
+    fn has_sequence(asm: &str, sequence: &[&str]) -> bool {
+        instructions(asm).windows(sequence.len()).any(|window| window.iter().zip(sequence).all(|(line, expected)| line == expected))
+    }
+
+    // the expression with every binary expression in parentheses
+    fn shape(expression: &Expression) -> String {
+        match expression {
+            Expression::Unary(unary) => match unary.as_ref() {
+                UnaryExpression::Nanpa(nanpa) => nanpa.value.to_string(),
+                UnaryExpression::Nimi(nimi) => nimi.value.clone(),
+                UnaryExpression::Group(group) => shape(&group.expr),
+                UnaryExpression::Negative(negative) => format!("-{}", shape(&negative.expr)),
+                UnaryExpression::Lon(lon) => if lon.value { "lon" } else { "ala" }.to_string(),
+                unary => panic!("no shape for {unary:?}"),
+            },
+            Expression::Binary(binary) => format!("({} {:?} {})", shape(&binary.lhs), binary.kind, shape(&binary.rhs)),
+        }
+    }
+
+    #[test]
+    fn operators_of_the_same_precedence_group_to_the_left() {
+        assert_eq!(shape(&parse_expression("A - B - C")), "((A Subtract B) Subtract C)");
+        assert_eq!(shape(&parse_expression("A / B * C")), "((A Divide B) Multiply C)");
+        assert_eq!(shape(&parse_expression("A << 1 >> 2")), "((A ShiftLeft 1) ShiftRight 2)");
+    }
+
+    #[test]
+    fn tighter_operators_are_grouped_first() {
+        assert_eq!(shape(&parse_expression("A + B * C - D")), "((A Add (B Multiply C)) Subtract D)");
+        assert_eq!(shape(&parse_expression("A | B ^ C & D")), "(A BitOr (B BitXor (C BitAnd D)))");
+        assert_eq!(shape(&parse_expression("A + 1 < B en B == C anu D")), "((((A Add 1) LessThan B) And (B Equals C)) Or D)");
+        assert_eq!(shape(&parse_expression("(A - B) * -C")), "((A Subtract B) Multiply -C)");
+    }
+
+    #[test]
+    fn telo_is_returned_in_xmm0() {
+        let asm = compile_source("pali g li pana e telo li pali e ni\n\to weka e 1.5\no pini\n").unwrap();
+        assert!(has_sequence(&asm, &["mov r8, 1069547520", "push r8", "movq xmm0, qword [rsp]", "add rsp, 8", "mov rsp, qword rbp"]));
+    }
+
+    #[test]
+    fn telo_arguments_go_in_xmm_registers_and_the_rest_in_integer_registers() {
+        let asm = compile_source(
+            "pali f li kepeken telo A en nanpa B en telo tu C li pana e telo li pali e ni\n\to weka e A\no pini\n\n\
+             pali lawa li pali e ni\n\to sin e telo T li kama sama o f e 1.5 e 2 e 2.5 a\no pini\n",
+        )
+        .unwrap();
+        // f takes them from the registers
+        assert!(has_sequence(&asm, &["sub rsp, 8", "movq qword [rsp], xmm0", "push qword rdi", "sub rsp, 8", "movq qword [rsp], xmm1"]));
+        // lawa hands them over, and takes the result from xmm0
+        assert!(has_sequence(&asm, &["movq xmm1, qword [rsp]", "add rsp, 8", "pop qword rdi", "movq xmm0, qword [rsp]", "add rsp, 8", "call f"]));
+        assert!(has_sequence(&asm, &["call f", "sub rsp, 8", "movq qword [rsp], xmm0"]));
+    }
+
+    #[test]
+    fn returns_have_to_fit_the_return_type() {
+        assert_eq!(compile_error("pali g li pana e telo li pali e ni\n\to sin e nanpa N li kama sama 1\n\to weka e N\no pini\n"), "E0404");
+        assert_eq!(compile_error("pali g li pali e ni\n\to weka e 1\no pini\n"), "E0414");
+        assert_eq!(compile_error("pali g li pana e nanpa li pali e ni\n\to weka\no pini\n"), "E0414");
+        // falling off the end is still allowed
+        assert!(compile_source("pali g li pana e nanpa li pali e ni\no pini\n").is_ok());
+    }
+
+    #[test]
+    fn a_lon_pali_does_not_give_back_numbers() {
+        assert_eq!(compile_error("pali f li pana e lon li pali e ni\n\to weka e 5\no pini\n"), "E0404");
+        assert_eq!(compile_error("pali f li pana e lon li pali e ni\n\to weka e ala + 1\no pini\n"), "E0404");
+        let asm = compile_source("pali f li pana e lon li pali e ni\n\to weka e 1 < 2\no pini\n").unwrap();
+        assert!(has_sequence(&asm, &["mov r8, 1", "push r8", "pop qword rax"]));
+    }
+    #[test]
+    fn every_broken_line_is_reported() {
+        let abstracter = tokenize("Y li 4\no sin nanpa Z\nY li kama sama 1");
+        let codes: Vec<&str> = abstracter.diagnostics.iter().map(|diagnostic| diagnostic.code).collect();
+        assert_eq!(codes, ["E0209", "E0202"]);
+    }
+
+
+    #[test]
+    fn a_broken_statement_ends_with_its_line() {
+        let diagnostics = compile_source(
+            "pali lawa li pali e ni\n\to sin e nanpa X li kama sama 1 +\n\tX li kama sama * 1\n\tX li kama sama * 2\n\tX li kama sama * 3\no pini\n",
+        )
+        .unwrap_err();
+        let errors: Vec<(&str, usize)> = diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.code, diagnostic.span.as_ref().unwrap().line))
+            .collect();
+        assert_eq!(errors, [("E0316", 2), ("E0304", 3), ("E0304", 4), ("E0304", 5)]);
+    }
+
+    #[test]
+    fn ala_before_a_minus_is_zero() {
+        assert_eq!(shape(&parse_expression("ala - 1")), "(ala Subtract 1)");
+        assert_eq!(shape(&parse_expression("ala + 1")), "(ala Add 1)");
+        let asm = compile_source("pali lawa li pana e nanpa li pali e ni\n\to sin e nanpa Y li kama sama ala - 1\n\to weka e Y\no pini\n").unwrap();
+        assert!(has_sequence(&asm, &["mov r8, -1", "push r8"]));
+    }
+
+    #[test]
+    fn ala_at_the_end_of_a_line_does_not_take_the_next_line() {
+        assert!(compile_source("o sin e lon X li kama sama ala\nX li kama sama lon\n").is_ok());
+    }
+
+    #[test]
+    fn asen_only_goes_in_a_pali() {
+        assert_eq!(compile_error("asen \"nop\"\n"), "E0415");
+        let asm = compile_source("pali lawa li pali e ni\n\to sin e nanpa X li kama sama 1\n\tasen \"inc {X}\"\no pini\n").unwrap();
+        assert!(has_sequence(&asm, &["inc qword [rbp - 8]"]));
+    }
+
+    #[test]
+    fn escapes_in_string_literals() {
+        assert_eq!(lex(r#""a\tb\n\"\\\0""#).unwrap(), vec![Word::StringLiteral("a\tb\n\"\\\0".to_string())]);
+        assert_eq!(lex(r#""\x41\u{e9}\u{1F600}""#).unwrap(), vec![Word::StringLiteral("Aé😀".to_string())]);
+    }
+
+    #[test]
+    fn broken_escapes_are_errors() {
+        assert_eq!(lex(r#""\x4""#).unwrap_err().code, "E0104");
+        assert_eq!(lex(r#""\x80""#).unwrap_err().code, "E0104");
+        assert_eq!(lex(r#""\u{110000}""#).unwrap_err().code, "E0105");
+        assert_eq!(lex(r#""\u41""#).unwrap_err().code, "E0105");
+        assert_eq!(lex(r#""\"#).unwrap_err().code, "E0102");
+    }
+
+    #[test]
+    fn raw_strings_keep_everything_up_to_their_end() {
+        assert_eq!(lex("r\"a\\n\nb\"").unwrap(), vec![Word::StringLiteral("a\\n\nb".to_string())]);
+        assert_eq!(lex("r#\"say \"hi\"\"#").unwrap(), vec![Word::StringLiteral("say \"hi\"".to_string())]);
+        assert_eq!(lex("r#\"a\"").unwrap_err().code, "E0102");
+    }
+
+    #[test]
+    fn nasin_nanpa_pona_adds_up_the_words() {
+        assert_eq!(tokenize_number("o weka e ale mute mute luka tu wan").unwrap(), "148");
+        assert_eq!(tokenize_number("o weka e luka luka").unwrap(), "10");
+        assert_eq!(tokenize_number("o weka e tu luka").unwrap_err()[0].code, "E0211");
+    }
+
+    #[test]
+    fn nasin_nanpa_lili_has_wan_tu_and_mute() {
+        assert_eq!(tokenize_number("kepeken nasin nanpa lili\no weka e tu wan").unwrap(), "3");
+        assert_eq!(tokenize_number("kepeken nasin nanpa lili\no weka e mute").unwrap(), "4");
+        assert_eq!(tokenize_number("kepeken nasin nanpa lili\no weka e luka").unwrap_err()[0].code, "E0210");
+        assert_eq!(tokenize_number("kepeken nasin nanpa lili\no weka e ale").unwrap_err()[0].code, "E0210");
+    }
+
+    #[test]
+    fn number_literals_take_a_prefix_and_underscores() {
+        assert_eq!(Parser::parse_number_literal("1_000"), Some(1000));
+        assert_eq!(Parser::parse_number_literal("0xff"), Some(255));
+        assert_eq!(Parser::parse_number_literal("0b1010"), Some(10));
+        assert_eq!(Parser::parse_number_literal("0o17"), Some(15));
+        assert_eq!(Parser::parse_number_literal("0x"), None);
+        assert_eq!(Parser::parse_number_literal("0b102"), None);
+        assert_eq!(Parser::parse_number_literal("99999999999999999999999999999999999999999"), None);
+    }
+
+    #[test]
+    fn tenpo_ale_checks_before_every_round() {
+        let asm = compile_source(
+            "pali lawa li pana e nanpa li pali e ni\n\to sin e nanpa N li kama sama 0\n\
+             \ttenpo ale pi N < 10 la\n\t\tN li kama sama N + 1\n\to pini\n\to weka e N\no pini\n",
+        )
+        .unwrap();
+        assert!(has_sequence(&asm, &[".loop_1:", "mov r8, qword [rbp - 8]"]));
+        assert!(has_sequence(&asm, &["setl cl", "push qword rcx", "pop qword rax", "cmp rax, 0", "je .endloop_1"]));
+        assert!(has_sequence(&asm, &["mov [rbp - 8], qword r9", "add rsp, 0", "jmp .loop_1", ".endloop_1:"]));
+        assert_eq!(compile_error("pali lawa li pali e ni\n\ttenpo ale pi 1 la\n\to pini\no pini\n"), "E0410");
+    }
+
+    #[test]
+    fn ante_la_runs_when_the_condition_is_false() {
+        let asm = compile_source(
+            "pali lawa li pana e nanpa li pali e ni\n\ttenpo pi 1 == 2 la\n\t\to weka e 1\n\tante la\n\t\to weka e 2\n\to pini\n\to weka e 3\no pini\n",
+        )
+        .unwrap();
+        assert!(has_sequence(&asm, &["cmp rax, 0", "je .else_1"]));
+        assert!(has_sequence(&asm, &["jmp .endif_1", ".else_1:", "mov r8, 2"]));
+        assert!(has_sequence(&asm, &[".endif_1:", "mov r8, 3"]));
+        assert_eq!(compile_error("pali lawa li pali e ni\n\tante la\n\to pini\no pini\n"), "E0313");
+    }
+
+    #[test]
+    fn awen_are_folded_and_written_to_rodata() {
+        let asm = compile_source(
+            "o sin e nanpa lukin awen SULI li kama sama 10 * 4\no sin e telo awen HALF li kama sama 1.0 / 2\n\n\
+             pali lawa li pana e nanpa li pali e ni\n\to sin e telo T li kama sama HALF\n\to weka e SULI + 2\no pini\n",
+        )
+        .unwrap();
+        // no awen has a stack slot, its uses are replaced by the value
+        assert!(has_sequence(&asm, &["mov r8, 1056964608", "push r8"]));
+        assert!(has_sequence(&asm, &["mov r8, 42", "push r8", "pop qword rax"]));
+        assert!(has_sequence(&asm, &["section '.rodata'", "public SULI", "SULI dq 40", "HALF dd 1056964608"]));
+    }
+
+    #[test]
+    fn awen_can_not_change() {
+        assert_eq!(compile_error("pali lawa li pali e ni\n\to sin e nanpa awen A li kama sama 1\n\tA li kama sama 2\no pini\n"), "E0411");
+        assert_eq!(compile_error("o sin e nanpa awen A\n"), "E0310");
+        assert_eq!(compile_error("pali f li pana e nanpa li pali e ni\n\to weka e 1\no pini\no sin e nanpa awen A li kama sama o f a\n"), "E0412");
+        assert_eq!(compile_error("o sin e lili awen A li kama sama 300\n"), "E0409");
+    }
+
+    #[test]
+    fn globals_go_to_data_and_bss() {
+        let asm = compile_source(
+            "o sin e nanpa lukin Count li kama sama 7\no sin e lili Flag\no sin e linja Name li kama sama \"pu\"\n\n\
+             pali lawa li pana e nanpa li pali e ni\n\tCount li kama sama Count + 1\n\tFlag li kama sama 3\n\to weka e Count\no pini\n",
+        )
+        .unwrap();
+        assert!(has_sequence(&asm, &["mov r8, qword [Count]"]));
+        assert!(has_sequence(&asm, &["pop qword r9", "mov [Count], qword r9"]));
+        assert!(has_sequence(&asm, &["pop qword r9", "mov [Flag], byte r9b"]));
+        assert!(has_sequence(&asm, &["section '.data' writeable align 8", "align 8", "public Count", "Count dq 7", "align 8", "Name dq 2, __tp_linja_0"]));
+        // only lukin globals are public
+        assert!(has_sequence(&asm, &["section '.bss' writeable align 8", "align 8", "Flag rb 1"]));
+    }
+
+    #[test]
+    fn globals_start_with_values_known_at_compile_time() {
+        assert_eq!(compile_error("pali f li pana e nanpa li pali e ni\n\to weka e 1\no pini\no sin e nanpa A li kama sama o f a\n"), "E0412");
+        assert_eq!(compile_error("pali lawa li pali e ni\n\to sin e nanpa lukin A\no pini\n"), "E0413");
+        assert_eq!(compile_error("o sin e nanpa A\no sin e nanpa A\n"), "E0403");
     }
 }